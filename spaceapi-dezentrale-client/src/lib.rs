@@ -3,7 +3,11 @@ extern crate rocket;
 
 use reqwest::StatusCode;
 use spaceapi_dezentrale::Status;
-use spaceapi_dezentrale_server::routes::KeepOpenResponse;
+use spaceapi_dezentrale_server::{
+    members::MemberStatus,
+    routes::{CheckInResponse, ConflictResponse, KeepOpenResponse, LeaseResponse},
+    state::Mode,
+};
 use std::time::{Duration, SystemTime};
 
 #[derive(Default)]
@@ -54,12 +58,30 @@ pub struct Client {
     client: reqwest::Client,
 }
 
+/// Reason of a change the server rejected with `409 Conflict`
+async fn conflict(response: reqwest::Response) -> String {
+    match response.json::<ConflictResponse>().await {
+        Ok(conflict) => {
+            let mut reason = format!("Space is {}", conflict.state);
+            if let Some(message) = conflict.message {
+                reason.push_str(&format!(" ({message})"));
+            }
+            if let Some(until) = conflict.until {
+                let until = SystemTime::UNIX_EPOCH + Duration::from_secs(until);
+                reason.push_str(&format!(" till {}", humantime::format_rfc3339_seconds(until)));
+            }
+            format!("{reason}: {}", conflict.error)
+        }
+        Err(err) => format!("Space can't be changed right now: {err}"),
+    }
+}
+
 impl Client {
     pub async fn open(&self) -> Result<(), String> {
         let url = format!(
             "{}{}",
             self.base_url,
-            uri!(spaceapi_dezentrale_server::routes::open_space())
+            uri!(spaceapi_dezentrale_server::routes::open_space(_, _))
         );
         let result = self
             .client
@@ -71,6 +93,8 @@ impl Client {
         match result.status() {
             StatusCode::OK => Ok(()),
             StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
             other => Err(format!("Unexpected status code return: {other}")),
         }
     }
@@ -79,7 +103,7 @@ impl Client {
         let url = format!(
             "{}{}",
             self.base_url,
-            uri!(spaceapi_dezentrale_server::routes::close_space())
+            uri!(spaceapi_dezentrale_server::routes::close_space(_, _))
         );
         let result = self
             .client
@@ -91,6 +115,30 @@ impl Client {
         match result.status() {
             StatusCode::OK => Ok(()),
             StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Signals that the client is alive, without changing the state
+    pub async fn heartbeat(&self) -> Result<(), String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::send_heartbeat())
+        );
+        let result = self
+            .client
+            .post(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't send heartbeat: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
             other => Err(format!("Unexpected status code return: {other}")),
         }
     }
@@ -137,11 +185,22 @@ impl Client {
         Ok(status)
     }
 
-    pub async fn keep_open(&self) -> Result<SystemTime, String> {
+    /// Keeps the space open with a lease, returns the end of the lease
+    ///
+    /// The lease is named after the API key if `lease` isn't set and lasts for the keep open
+    /// interval of the server if `duration` isn't set.
+    pub async fn keep_open(
+        &self,
+        lease: Option<&str>,
+        duration: Option<Duration>,
+    ) -> Result<SystemTime, String> {
         let url = format!(
             "{}{}",
             self.base_url,
-            uri!(spaceapi_dezentrale_server::routes::keep_open())
+            uri!(spaceapi_dezentrale_server::routes::keep_open(
+                lease,
+                duration.map(|duration| duration.as_secs())
+            ))
         );
         let result = self
             .client
@@ -161,6 +220,200 @@ impl Client {
                 Ok(open_till)
             }
             StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Leases which keep the space open
+    pub async fn leases(&self) -> Result<Vec<LeaseResponse>, String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::list_leases())
+        );
+        let result = self
+            .client
+            .get(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't get leases: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => result
+                .json::<Vec<LeaseResponse>>()
+                .await
+                .map_err(|err| format!("Can't parse response {err}")),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Revokes a lease, the space closes if no other lease keeps it open
+    pub async fn revoke_lease(&self, lease: &str) -> Result<(), String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::revoke_lease(lease))
+        );
+        let result = self
+            .client
+            .delete(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't revoke lease: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            StatusCode::NOT_FOUND => Err(format!("Lease `{lease}` doesn't exist")),
+            StatusCode::CONFLICT => Err(conflict(result).await),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Sets a mode, the space is published as closed and open requests are rejected
+    ///
+    /// The mode lasts till `until` if set, otherwise till it's lifted.
+    pub async fn set_mode(
+        &self,
+        mode: Mode,
+        message: Option<&str>,
+        until: Option<SystemTime>,
+    ) -> Result<(), String> {
+        let until = until
+            .map(|until| until.duration_since(SystemTime::UNIX_EPOCH))
+            .transpose()
+            .map_err(|err| format!("Invalid end of the mode: {err}"))?
+            .map(|until| until.as_secs());
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::set_mode(
+                mode.name(),
+                message,
+                until
+            ))
+        );
+        let result = self
+            .client
+            .post(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't set mode: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            StatusCode::BAD_REQUEST => Err("The end of the mode is in the past".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Lifts the current mode, the space stays closed
+    pub async fn lift_mode(&self) -> Result<(), String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::lift_mode())
+        );
+        let result = self
+            .client
+            .delete(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't lift mode: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key is read only, request denied".to_string()),
+            StatusCode::NOT_FOUND => Err("No mode is set".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Detailed status of the members view, readable with API keys of any scope
+    pub async fn member_status(&self) -> Result<MemberStatus, String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::get_member_status())
+        );
+        let result = self
+            .client
+            .get(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't get member status: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => result
+                .json::<MemberStatus>()
+                .await
+                .map_err(|err| format!("Can't parse response {err}")),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::NOT_FOUND => Err("The members view isn't enabled".to_string()),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Checks in `member`, the name of the API key by default, till the server checks out
+    /// automatically
+    ///
+    /// The name is only published if `public` is set.
+    pub async fn check_in(&self, member: Option<&str>, public: bool) -> Result<CheckInResponse, String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::check_in(member, Some(public)))
+        );
+        let result = self
+            .client
+            .post(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't check in: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => result
+                .json::<CheckInResponse>()
+                .await
+                .map_err(|err| format!("Can't parse response {err}")),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key can't check in other members, request denied".to_string()),
+            StatusCode::NOT_FOUND => Err("Check-in isn't enabled".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
+            other => Err(format!("Unexpected status code return: {other}")),
+        }
+    }
+
+    /// Checks out `member`, the name of the API key by default
+    pub async fn check_out(&self, member: Option<&str>) -> Result<(), String> {
+        let url = format!(
+            "{}{}",
+            self.base_url,
+            uri!(spaceapi_dezentrale_server::routes::check_out(member))
+        );
+        let result = self
+            .client
+            .post(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|err| format!("Can't check out: {err:?}"))?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err("Wrong API-Key provided, request denied".to_string()),
+            StatusCode::FORBIDDEN => Err("API-Key can't check out other members, request denied".to_string()),
+            StatusCode::NOT_FOUND => Err("Not checked in, or check-in isn't enabled".to_string()),
+            StatusCode::CONFLICT => Err(conflict(result).await),
             other => Err(format!("Unexpected status code return: {other}")),
         }
     }
@@ -1,4 +1,4 @@
-use clap::Command;
+use clap::{Arg, Command};
 
 #[tokio::main]
 async fn main() {
@@ -8,7 +8,65 @@ async fn main() {
         .about("A client for changing space status")
         .subcommand(Command::new("open"))
         .subcommand(Command::new("close"))
-        .subcommand(Command::new("keep-open"))
+        .subcommand(
+            Command::new("keep-open")
+                .arg(
+                    Arg::new("lease")
+                        .long("lease")
+                        .help("Id of the lease, the name of the API key by default"),
+                )
+                .arg(
+                    Arg::new("for")
+                        .long("for")
+                        .value_parser(humantime::parse_duration)
+                        .help("Duration of the lease, e.g. `3h`, bounded by the server"),
+                ),
+        )
+        .subcommand(Command::new("leases"))
+        .subcommand(Command::new("revoke-lease").arg(Arg::new("lease").required(true)))
+        .subcommand(
+            Command::new("mode")
+                .arg(
+                    Arg::new("mode")
+                        .required(true)
+                        .value_parser(["maintenance", "private_event"]),
+                )
+                .arg(
+                    Arg::new("message")
+                        .long("message")
+                        .help("Message published while the mode lasts"),
+                )
+                .arg(
+                    Arg::new("for")
+                        .long("for")
+                        .value_parser(humantime::parse_duration)
+                        .help("Duration of the mode, e.g. `2d`, till it's lifted by default"),
+                ),
+        )
+        .subcommand(Command::new("lift-mode"))
+        .subcommand(Command::new("members"))
+        .subcommand(
+            Command::new("check-in")
+                .arg(
+                    Arg::new("member")
+                        .long("member")
+                        .help("Name of the member, the name of the API key by default"),
+                )
+                .arg(
+                    Arg::new("public")
+                        .long("public")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Publish the name of the member"),
+                ),
+        )
+        .subcommand(
+            Command::new("check-out").arg(
+                Arg::new("member")
+                    .long("member")
+                    .help("Name of the member, the name of the API key by default"),
+            ),
+        )
+        .subcommand(Command::new("heartbeat"))
         .subcommand(Command::new("is-open"));
     let args = prog.get_matches();
 
@@ -20,28 +78,99 @@ async fn main() {
         .build()
         .expect("Can't build spaceapi client");
 
-    match args.subcommand_name() {
-        Some("open") => {
+    match args.subcommand() {
+        Some(("open", _)) => {
             client.open().await.expect("Open failed");
         }
-        Some("close") => {
+        Some(("close", _)) => {
             client.close().await.expect("Close failed");
         }
-        Some("is-open") => {
+        Some(("is-open", _)) => {
             if client.is_open().await.expect("Request failed") {
                 println!("open");
             } else {
                 println!("closed");
             }
         }
-        Some("keep-open") => {
+        Some(("keep-open", args)) => {
+            let lease = args.get_one::<String>("lease");
+            let duration = args.get_one::<std::time::Duration>("for");
             client
-                .keep_open()
+                .keep_open(lease.map(String::as_str), duration.copied())
                 .await
-                .map(|till| println!("Space is open till {till:?}"))
+                .map(|till| println!("Space is open till {}", humantime::format_rfc3339_seconds(till)))
                 .expect("Request failed");
         }
-        Some(other) => {
+        Some(("heartbeat", _)) => {
+            client.heartbeat().await.expect("Heartbeat failed");
+        }
+        Some(("leases", _)) => {
+            for lease in client.leases().await.expect("Request failed") {
+                println!("{} ({}) till {}", lease.id, lease.key, lease.until);
+            }
+        }
+        Some(("revoke-lease", args)) => {
+            let lease = args.get_one::<String>("lease").expect("Required lease");
+            client.revoke_lease(lease).await.expect("Revoke failed");
+        }
+        Some(("mode", args)) => {
+            let mode = args
+                .get_one::<String>("mode")
+                .expect("Required mode")
+                .parse()
+                .expect("Known mode");
+            let message = args.get_one::<String>("message");
+            let until = args
+                .get_one::<std::time::Duration>("for")
+                .map(|duration| std::time::SystemTime::now() + *duration);
+            client
+                .set_mode(mode, message.map(String::as_str), until)
+                .await
+                .expect("Request failed");
+        }
+        Some(("lift-mode", _)) => {
+            client.lift_mode().await.expect("Request failed");
+        }
+        Some(("members", _)) => {
+            let status = client.member_status().await.expect("Request failed");
+            println!("{} since {}", status.state, status.since);
+            if let Some(person) = status.trigger_person {
+                println!("by {person}");
+            }
+            if let Some(message) = status.message {
+                println!("{message}");
+            }
+            for member in status.present {
+                println!("present {member}");
+            }
+            for lease in status.leases {
+                println!("lease {} ({}) till {}", lease.id, lease.key, lease.until);
+            }
+            for change in status.history {
+                let state = if change.open { "open" } else { "closed" };
+                println!(
+                    "{} {state} {}",
+                    change.timestamp,
+                    change.trigger_person.unwrap_or_default()
+                );
+            }
+        }
+        Some(("check-in", args)) => {
+            let member = args.get_one::<String>("member");
+            let check_in = client
+                .check_in(member.map(String::as_str), args.get_flag("public"))
+                .await
+                .expect("Request failed");
+            println!("{} checked in till {}", check_in.member, check_in.until);
+        }
+        Some(("check-out", args)) => {
+            let member = args.get_one::<String>("member");
+            client
+                .check_out(member.map(String::as_str))
+                .await
+                .expect("Request failed");
+        }
+        Some((other, _)) => {
             println!("Unknown command `{other}`");
         }
         None => {
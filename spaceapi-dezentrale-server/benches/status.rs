@@ -0,0 +1,98 @@
+//! Compares serializing the status per request with the pre-rendered snapshots
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use spaceapi_dezentrale_server::{
+    config::{PrivacyConfig, StatusDisplay, StatusDisplayTypes},
+    display::StatusDisplays,
+    history::History,
+    snapshot,
+    state::{SpaceGuard, SpaceState},
+};
+use std::time::{Duration, SystemTime};
+
+fn template() -> spaceapi_dezentrale::Status {
+    let mut contact = spaceapi_dezentrale::Contact {
+        email: Some("info@example.org".to_string()),
+        irc: Some("irc://irc.example.org/#space".to_string()),
+        ml: Some("members@example.org".to_string()),
+        ..Default::default()
+    };
+    contact.phone = Some("+49 123 456789".to_string());
+    spaceapi_dezentrale::StatusBuilder::v14("Example space")
+        .logo("https://example.org/logo.svg")
+        .url("https://example.org")
+        .contact(contact)
+        .location(spaceapi_dezentrale::Location {
+            address: Some("Example street 1, 12345 Example city".to_string()),
+            lat: 51.0,
+            lon: 13.7,
+            timezone: Some("Europe/Berlin".to_string()),
+        })
+        .add_project("https://github.com/example/project")
+        .add_project("https://github.com/example/another-project")
+        .build()
+        .expect("Valid template")
+}
+
+fn displays() -> StatusDisplays {
+    StatusDisplays::new(&StatusDisplayTypes {
+        text: StatusDisplay {
+            open: "open since {{ lastchange | datetime }}".to_string(),
+            closed: "closed".to_string(),
+            ..StatusDisplay::default()
+        },
+        ..StatusDisplayTypes::default()
+    })
+    .expect("Valid templates")
+}
+
+/// What `/spaceapi/v14` did before the cache: clone the template and serialize it on every request
+fn serialize_per_request(template: &spaceapi_dezentrale::Status, state: &SpaceState) -> Vec<u8> {
+    let mut status = template.clone();
+    status.api_compatibility = Some(vec![spaceapi_dezentrale::ApiVersion::V14]);
+    status.state = Some(spaceapi_dezentrale::State {
+        open: state.status.open(),
+        trigger_person: state.trigger_person.clone(),
+        message: state.message.clone(),
+        ..spaceapi_dezentrale::State::default()
+    });
+    rocket::serde::json::serde_json::to_vec(&status).expect("Serializable status")
+}
+
+fn status(c: &mut Criterion) {
+    let template = template();
+    let displays = displays();
+    let privacy = PrivacyConfig::default();
+    let state = SpaceState::new(Duration::from_secs(300));
+    let space = SpaceGuard::new(Duration::from_secs(300), History::default()).with_status_cache(
+        template.clone(),
+        displays.clone(),
+        PrivacyConfig::default(),
+    );
+    let receiver = space.subscribe().expect("Status cache");
+
+    let mut group = c.benchmark_group("spaceapi_v14");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("serialize_per_request", |b| {
+        b.iter(|| serialize_per_request(black_box(&template), black_box(&state)))
+    });
+    group.bench_function("render_snapshot", |b| {
+        b.iter(|| {
+            snapshot::render(
+                black_box(&template),
+                black_box(&displays),
+                black_box(&privacy),
+                black_box(&state),
+                None,
+                SystemTime::now(),
+            )
+        })
+    });
+    group.bench_function("cached_snapshot", |b| {
+        b.iter(|| black_box(&receiver).borrow().spaceapi.clone())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, status);
+criterion_main!(benches);
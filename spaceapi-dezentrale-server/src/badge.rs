@@ -0,0 +1,151 @@
+use crate::{
+    config::{BadgeDisplay, BadgePart, Color},
+    state::SpaceStatus,
+};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+const HEIGHT: u32 = 20;
+const PADDING: u32 = 6;
+/// Average glyph width of Verdana 11px used by the SVG badge
+const SVG_CHAR_WIDTH: u32 = 7;
+
+/// Right side of the badge for the status
+fn status_part(badge: &BadgeDisplay, status: SpaceStatus) -> &BadgePart {
+    match status {
+        SpaceStatus::Open => &badge.open,
+        SpaceStatus::KeepOpen(_) => badge.keep_open.as_ref().unwrap_or(&badge.open),
+        SpaceStatus::Closed => &badge.closed,
+        SpaceStatus::Unknown => &badge.unknown,
+        SpaceStatus::Maintenance(_) => &badge.maintenance,
+        SpaceStatus::PrivateEvent(_) => &badge.private_event,
+    }
+}
+
+/// Renders a shields.io styled SVG badge
+pub fn render_svg(badge: &BadgeDisplay, status: SpaceStatus) -> String {
+    let label = &badge.label;
+    let status = status_part(badge, status);
+    let label_width = label.text.chars().count() as u32 * SVG_CHAR_WIDTH + 2 * PADDING;
+    let status_width = status.text.chars().count() as u32 * SVG_CHAR_WIDTH + 2 * PADDING;
+    let width = label_width + status_width;
+    let label_text = crate::escape_xml(&label.text);
+    let status_text = crate::escape_xml(&status.text);
+    let label_color = String::from(label.color);
+    let status_color = String::from(status.color);
+    let label_x = label_width / 2;
+    let status_x = label_width + status_width / 2;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{HEIGHT}" role="img" aria-label="{label_text}: {status_text}">
+  <title>{label_text}: {status_text}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r"><rect width="{width}" height="{HEIGHT}" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="{HEIGHT}" fill="{label_color}"/>
+    <rect x="{label_width}" width="{status_width}" height="{HEIGHT}" fill="{status_color}"/>
+    <rect width="{width}" height="{HEIGHT}" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="14">{label_text}</text>
+    <text x="{status_x}" y="14">{status_text}</text>
+  </g>
+</svg>
+"##
+    )
+}
+
+/// RGB pixel buffer used as drawing target for the PNG badge
+struct Canvas {
+    width: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32) -> Self {
+        Canvas {
+            width,
+            pixels: vec![0; (width * HEIGHT * 3) as usize],
+        }
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(self.width, HEIGHT)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = Rgb888;
+    type Error = std::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) {
+                if x < self.width && y < HEIGHT {
+                    let idx = ((y * self.width + x) * 3) as usize;
+                    self.pixels[idx..idx + 3].copy_from_slice(&[color.r(), color.g(), color.b()]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rgb(color: Color) -> Rgb888 {
+    let [r, g, b] = color.0;
+    Rgb888::new(r, g, b)
+}
+
+/// Draws one side of the badge, returns the x offset after it
+fn draw_part(canvas: &mut Canvas, part: &BadgePart, x: u32) -> u32 {
+    let width = part.text.chars().count() as u32 * FONT_6X10.character_size.width + 2 * PADDING;
+    let _ = Rectangle::new(Point::new(x as i32, 0), Size::new(width, HEIGHT))
+        .into_styled(PrimitiveStyle::with_fill(rgb(part.color)))
+        .draw(canvas);
+    let text_style = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Middle)
+        .build();
+    let _ = Text::with_text_style(
+        &part.text,
+        Point::new((x + width / 2) as i32, (HEIGHT / 2) as i32),
+        MonoTextStyle::new(&FONT_6X10, Rgb888::WHITE),
+        text_style,
+    )
+    .draw(canvas);
+    x + width
+}
+
+/// Renders the badge as PNG image
+///
+/// The text is drawn with a builtin bitmap font which only supports ASCII.
+pub fn render_png(badge: &BadgeDisplay, status: SpaceStatus) -> Result<Vec<u8>, String> {
+    let status = status_part(badge, status);
+    let text_width = |part: &BadgePart| part.text.chars().count() as u32 * FONT_6X10.character_size.width;
+    let mut canvas = Canvas::new(text_width(&badge.label) + text_width(status) + 4 * PADDING);
+    let x = draw_part(&mut canvas, &badge.label, 0);
+    draw_part(&mut canvas, status, x);
+
+    let mut image = vec![];
+    let mut encoder = png::Encoder::new(&mut image, canvas.width, HEIGHT);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&canvas.pixels))
+        .map_err(|err| format!("Can't encode badge: {err}"))?;
+    Ok(image)
+}
@@ -0,0 +1,142 @@
+use crate::state::SpaceState;
+use rocket::{
+    http::{
+        hyper::header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+        Header, Status,
+    },
+    request::{self, FromRequest, Request},
+    response::{self, Responder, Response},
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    time::{Duration, SystemTime},
+};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+fn format_http_date(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format(HTTP_DATE_FORMAT)
+        .to_string()
+}
+
+fn parse_http_date(value: &str) -> Option<u64> {
+    let time = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    u64::try_from(time.timestamp()).ok()
+}
+
+/// Cache validators of a response, derived from the state of the space
+#[derive(Debug, Clone)]
+pub struct Validators {
+    etag: String,
+    last_modified: SystemTime,
+}
+
+impl Validators {
+    /// The ETag covers everything a status route publishes, the last modification is `now` unless
+    /// the ETag is the one of the `previous` validators
+    pub fn new(state: &SpaceState, previous: Option<&Validators>, now: SystemTime) -> Self {
+        let mut hasher = DefaultHasher::new();
+        state.status.hash(&mut hasher);
+        state.lastchange.hash(&mut hasher);
+        state.trigger_person.hash(&mut hasher);
+        state.message.hash(&mut hasher);
+        state.closing_soon.hash(&mut hasher);
+        state.sensor_values.hash(&mut hasher);
+        if let Some(presence) = &state.presence {
+            presence.count().hash(&mut hasher);
+            presence.public_names().hash(&mut hasher);
+        }
+        let etag = format!("\"{:016x}\"", hasher.finish());
+        let last_modified = match previous {
+            Some(previous) if previous.etag == etag => previous.last_modified,
+            _ => now,
+        };
+        Validators { etag, last_modified }
+    }
+}
+
+/// Validators sent by the client with a conditional request
+pub struct Conditional<'r> {
+    if_none_match: Option<&'r str>,
+    if_modified_since: Option<&'r str>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Conditional<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(Conditional {
+            if_none_match: req.headers().get_one(IF_NONE_MATCH.as_str()),
+            if_modified_since: req.headers().get_one(IF_MODIFIED_SINCE.as_str()),
+        })
+    }
+}
+
+impl Conditional<'_> {
+    /// Whether the client has the current version already
+    ///
+    /// `If-Modified-Since` is only used without `If-None-Match`, see RFC 9110.
+    pub fn is_not_modified(&self, validators: &Validators) -> bool {
+        if let Some(if_none_match) = self.if_none_match {
+            return if_none_match
+                .split(',')
+                .map(|etag| etag.trim().trim_start_matches("W/"))
+                .any(|etag| etag == "*" || etag == validators.etag);
+        }
+        self.if_modified_since
+            .and_then(parse_http_date)
+            .is_some_and(|since| crate::unix_timestamp(validators.last_modified) <= since)
+    }
+}
+
+/// Response with cache headers, without a body if the client has the current version already
+pub struct Cached<R> {
+    body: Option<R>,
+    validators: Validators,
+    max_age: Duration,
+}
+
+impl<R> Cached<R> {
+    pub fn new(body: R, validators: Validators, max_age: Duration) -> Self {
+        Cached {
+            body: Some(body),
+            validators,
+            max_age,
+        }
+    }
+
+    pub fn not_modified(validators: Validators, max_age: Duration) -> Self {
+        Cached {
+            body: None,
+            validators,
+            max_age,
+        }
+    }
+
+    fn cache_control(&self) -> String {
+        match self.max_age.as_secs() {
+            0 => "no-cache".to_string(),
+            max_age => format!("public, max-age={max_age}"),
+        }
+    }
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Cached<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let cache_control = self.cache_control();
+        let mut response = match self.body {
+            Some(body) => body.respond_to(req)?,
+            None => Response::build().status(Status::NotModified).finalize(),
+        };
+        response.set_header(Header::new(ETAG.as_str(), self.validators.etag.clone()));
+        response.set_header(Header::new(
+            LAST_MODIFIED.as_str(),
+            format_http_date(self.validators.last_modified),
+        ));
+        response.set_header(Header::new(CACHE_CONTROL.as_str(), cache_control));
+        Ok(response)
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    config::{CalendarConfig, PrivacyConfig, ScheduleSlot},
+    history::History,
+};
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::{fmt::Write, time::SystemTime};
+
+/// Maximum length of a content line in octets
+const LINE_LENGTH: usize = 75;
+
+/// Generator of the iCalendar feed with the opening times
+pub struct Calendar {
+    schedule: Vec<ScheduleSlot>,
+    weeks: u32,
+    timezone: Tz,
+    space: String,
+    location: Option<String>,
+    /// Leaves out the messages of the openings
+    hide_message: bool,
+}
+
+/// Opening interval shown as event
+struct Opening {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    summary: String,
+    description: Option<String>,
+    planned: bool,
+}
+
+impl Calendar {
+    /// Uses the timezone of the location of the space, UTC if not set
+    pub fn new(
+        config: &CalendarConfig,
+        template: &spaceapi_dezentrale::Status,
+        privacy: &PrivacyConfig,
+    ) -> Result<Self, String> {
+        let timezone = match &template.location.timezone {
+            Some(timezone) => timezone
+                .parse::<Tz>()
+                .map_err(|err| format!("Invalid timezone `{timezone}`: {err}"))?,
+            None => Tz::UTC,
+        };
+        Ok(Calendar {
+            schedule: config.schedule.clone(),
+            weeks: config.weeks,
+            timezone,
+            space: template.space.clone(),
+            location: template.location.address.clone(),
+            hide_message: privacy.hide_message,
+        })
+    }
+
+    /// Opening intervals recorded in the history
+    fn past_openings(&self, history: &History, now: DateTime<Utc>) -> Vec<Opening> {
+        let mut openings = vec![];
+        let mut opened = None;
+        for change in history.published() {
+            let timestamp = DateTime::from_timestamp(change.timestamp as i64, 0).unwrap_or_default();
+            match (change.open, opened.take()) {
+                (true, None) => opened = Some((timestamp, change)),
+                (true, Some(open)) => opened = Some(open),
+                (false, Some((start, change))) => openings.push(Opening {
+                    start,
+                    end: timestamp,
+                    summary: format!("{} open", self.space),
+                    description: change.message.clone().filter(|_| !self.hide_message),
+                    planned: false,
+                }),
+                (false, None) => {}
+            }
+        }
+        if let Some((start, change)) = opened {
+            openings.push(Opening {
+                start,
+                end: now,
+                summary: format!("{} open", self.space),
+                description: change.message.clone().filter(|_| !self.hide_message),
+                planned: false,
+            });
+        }
+        openings
+    }
+
+    /// Planned opening slots of the next weeks which didn't end yet
+    fn planned_openings(&self, now: DateTime<Utc>) -> Vec<Opening> {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        let mut openings = vec![];
+        for date in today.iter_days().take(self.weeks as usize * 7) {
+            for slot in self.schedule.iter().filter(|slot| slot.weekday == date.weekday()) {
+                let end_date = if slot.end <= slot.start {
+                    date + Duration::days(1)
+                } else {
+                    date
+                };
+                let start = self
+                    .timezone
+                    .from_local_datetime(&date.and_time(slot.start))
+                    .earliest();
+                let end = self
+                    .timezone
+                    .from_local_datetime(&end_date.and_time(slot.end))
+                    .latest();
+                let (Some(start), Some(end)) = (start, end) else {
+                    continue;
+                };
+                let end = end.with_timezone(&Utc);
+                if end < now {
+                    continue;
+                }
+                openings.push(Opening {
+                    start: start.with_timezone(&Utc),
+                    end,
+                    summary: slot
+                        .summary
+                        .clone()
+                        .unwrap_or_else(|| format!("{} open (planned)", self.space)),
+                    description: None,
+                    planned: true,
+                });
+            }
+        }
+        openings
+    }
+
+    /// Renders the calendar with the history and the planned openings
+    pub fn render(&self, history: &History, now: SystemTime) -> String {
+        let now = DateTime::<Utc>::from(now);
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:-//dezentrale//{} {}//EN", crate::SOFTWARE, crate::VERSION),
+            "CALSCALE:GREGORIAN".to_string(),
+            format!(
+                "X-WR-CALNAME:{}",
+                escape_text(&format!("{} opening times", self.space))
+            ),
+            format!("X-WR-TIMEZONE:{}", self.timezone.name()),
+        ];
+
+        let openings = self.past_openings(history, now);
+        for opening in openings.iter().chain(self.planned_openings(now).iter()) {
+            let kind = if opening.planned { "planned" } else { "open" };
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!(
+                "UID:{kind}-{}-{}@{}",
+                opening.start.timestamp(),
+                opening.end.timestamp(),
+                crate::SOFTWARE
+            ));
+            lines.push(format!("DTSTAMP:{}", format_datetime(now)));
+            lines.push(format!("DTSTART:{}", format_datetime(opening.start)));
+            lines.push(format!("DTEND:{}", format_datetime(opening.end)));
+            lines.push(format!("SUMMARY:{}", escape_text(&opening.summary)));
+            if let Some(description) = &opening.description {
+                lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+            }
+            if let Some(location) = &self.location {
+                lines.push(format!("LOCATION:{}", escape_text(location)));
+            }
+            if opening.planned {
+                lines.push("STATUS:TENTATIVE".to_string());
+            } else {
+                lines.push("STATUS:CONFIRMED".to_string());
+            }
+            lines.push("END:VEVENT".to_string());
+        }
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().fold(String::new(), |mut calendar, line| {
+            let _ = write!(calendar, "{}\r\n", fold_line(line));
+            calendar
+        })
+    }
+}
+
+fn format_datetime(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a TEXT value (RFC 5545, section 3.3.11)
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
+
+/// Folds a content line to lines of at most 75 octets (RFC 5545, section 3.1)
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
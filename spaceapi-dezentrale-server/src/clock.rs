@@ -0,0 +1,65 @@
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+/// Source of the current time for the state of the space
+#[rocket::async_trait]
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> SystemTime;
+
+    /// Waits till the clock reached `deadline`
+    async fn sleep_until(&self, deadline: SystemTime);
+}
+
+/// Wall clock of the system
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[rocket::async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    async fn sleep_until(&self, deadline: SystemTime) {
+        let remaining = deadline.duration_since(SystemTime::now()).unwrap_or_default();
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+/// Clock which only moves when advanced, for deterministic tests
+#[derive(Debug)]
+pub struct ManualClock {
+    now: watch::Sender<SystemTime>,
+}
+
+impl ManualClock {
+    pub fn new(now: SystemTime) -> Self {
+        ManualClock {
+            now: watch::channel(now).0,
+        }
+    }
+
+    /// Moves the clock forward and wakes all sleepers whose deadline passed
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    }
+}
+
+#[rocket::async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.borrow()
+    }
+
+    async fn sleep_until(&self, deadline: SystemTime) {
+        let mut now = self.now.subscribe();
+        // The sender lives as long as the clock, which outlives its sleepers
+        let _ = now.wait_for(|now| *now >= deadline).await;
+    }
+}
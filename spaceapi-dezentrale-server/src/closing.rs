@@ -0,0 +1,65 @@
+use crate::config::ClosingSoonConfig;
+use minijinja::{context, Environment};
+use std::time::{Duration, SystemTime};
+
+const MINUTE: Duration = Duration::from_secs(60);
+const TEMPLATE: &str = "message";
+
+/// Minutes till `open_till` at `now`, rounded up
+pub fn minutes_left(now: SystemTime, open_till: SystemTime) -> u64 {
+    let remaining = open_till.duration_since(now).unwrap_or_default();
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    seconds.div_ceil(60)
+}
+
+/// Warning before a keep open request expires, see [`ClosingSoonConfig`]
+pub struct ClosingSoon {
+    period: Duration,
+    env: Environment<'static>,
+}
+
+impl ClosingSoon {
+    /// Compiles the message template, `None` if the warning is disabled
+    pub fn new(config: &ClosingSoonConfig) -> Result<Option<Self>, String> {
+        if config.period.is_zero() {
+            return Ok(None);
+        }
+        let mut env = Environment::new();
+        env.add_template_owned(TEMPLATE, config.message.clone())
+            .map_err(|err| format!("Invalid closing soon message: {err}"))?;
+        let closing_soon = ClosingSoon {
+            period: config.period,
+            env,
+        };
+        closing_soon.message(1)?;
+        Ok(Some(closing_soon))
+    }
+
+    /// Whether the warning of `open_till` started at `now`
+    pub fn is_warning(&self, now: SystemTime, open_till: SystemTime) -> bool {
+        now < open_till && now + self.period >= open_till
+    }
+
+    /// Time of the next change of the warning of `open_till` after `now`
+    ///
+    /// That's the start of the warning, or the next full minute left while it lasts.
+    pub fn next_tick(&self, now: SystemTime, open_till: SystemTime) -> SystemTime {
+        let start = open_till
+            .checked_sub(self.period)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if now < start {
+            return start;
+        }
+        let minutes = minutes_left(now, open_till);
+        let remaining = MINUTE * u32::try_from(minutes.saturating_sub(1)).unwrap_or(u32::MAX);
+        open_till.checked_sub(remaining).unwrap_or(open_till)
+    }
+
+    /// Renders the published message with the minutes left
+    pub fn message(&self, minutes: u64) -> Result<String, String> {
+        self.env
+            .get_template(TEMPLATE)
+            .and_then(|template| template.render(context! { minutes }))
+            .map_err(|err| format!("Can't render closing soon message: {err}"))
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    clock::Clock,
+    config::{CommandConfig, CommandStateConfig},
+    sensors::{self, SensorTarget},
+    source::{RequestedState, Runner},
+    state::SpaceGuard,
+};
+use rocket::serde::json::Value;
+use std::{process::Stdio, sync::Arc};
+use tokio::process::Command;
+
+/// Result of a finished command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub stdout: String,
+    /// Exit code, `None` if the command was killed by a signal
+    pub code: Option<i32>,
+}
+
+/// What a run of a command reported
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reading {
+    Sensor(SensorTarget, Value),
+    /// Whether the space is open
+    State(bool),
+}
+
+impl CommandOutput {
+    fn failure(&self) -> String {
+        match self.code {
+            Some(code) => format!("exited with {code}"),
+            None => "was killed".to_string(),
+        }
+    }
+
+    /// Output without surrounding whitespace, if the command succeeded
+    fn success(&self) -> Result<&str, String> {
+        match self.code {
+            Some(0) => Ok(self.stdout.trim()),
+            _ => Err(self.failure()),
+        }
+    }
+
+    /// Maps the result to open or closed
+    fn state(&self, config: &CommandStateConfig) -> Result<bool, String> {
+        if config.exit_code {
+            return match self.code {
+                Some(0) => Ok(true),
+                Some(1) => Ok(false),
+                _ => Err(self.failure()),
+            };
+        }
+        let stdout = self.success()?;
+        if config.open.iter().any(|open| open == stdout) {
+            Ok(true)
+        } else if config.closed.iter().any(|closed| closed == stdout) {
+            Ok(false)
+        } else {
+            Err(format!("reported the unknown state `{stdout}`"))
+        }
+    }
+}
+
+/// Command feeding a sensor or the state of the space, see [`CommandConfig`]
+#[derive(Clone)]
+pub struct CommandSource {
+    config: Arc<CommandConfig>,
+    clock: Arc<dyn Clock>,
+    /// Published sensors, to reject values which don't fit before they're published
+    template: Arc<Option<spaceapi_dezentrale::sensors::Sensors>>,
+    /// State requested by the previous runs, requests are only sent on changes
+    requested: Arc<RequestedState>,
+}
+
+impl CommandSource {
+    pub fn new(config: CommandConfig, template: &spaceapi_dezentrale::Status, clock: Arc<dyn Clock>) -> Self {
+        CommandSource {
+            config: Arc::new(config),
+            clock,
+            template: Arc::new(template.sensors.clone()),
+            requested: Arc::new(RequestedState::default()),
+        }
+    }
+
+    /// Runs the command once, it's killed when it exceeds the timeout
+    pub async fn run(&self) -> Result<CommandOutput, String> {
+        let name = &self.config.name;
+        let (program, args) = self
+            .config
+            .command
+            .split_first()
+            .ok_or_else(|| format!("Command `{name}` has no program"))?;
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| format!("Can't run command `{name}`: {err}"))?;
+        let output = tokio::time::timeout(self.config.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| format!("Command `{name}` timed out after {:?}", self.config.timeout))?
+            .map_err(|err| format!("Command `{name}` failed: {err}"))?;
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            code: output.status.code(),
+        })
+    }
+
+    /// Runs the command and maps its result
+    pub async fn read(&self) -> Result<Reading, String> {
+        let output = self.run().await?;
+        let name = &self.config.name;
+        if let Some(state) = &self.config.state {
+            return output
+                .state(state)
+                .map(Reading::State)
+                .map_err(|err| format!("Command `{name}` {err}"));
+        }
+        let target = self
+            .config
+            .sensor
+            .clone()
+            .ok_or_else(|| format!("Command `{name}` has neither a sensor nor a state"))?;
+        let stdout = output
+            .success()
+            .map_err(|err| format!("Command `{name}` {err}"))?;
+        let value = sensors::parse_value(stdout);
+        let mut published = (*self.template).clone();
+        target
+            .apply(&mut published, &value)
+            .map_err(|err| format!("Command `{name}`: {err}"))?;
+        Ok(Reading::Sensor(target, value))
+    }
+
+    /// Runs the command and publishes its result
+    ///
+    /// A state is only requested when it differs from the previous run, see [`RequestedState`]. A
+    /// failed run keeps the previous value.
+    pub async fn update(&self, space: &SpaceGuard) {
+        let reading = match self.read().await {
+            Ok(reading) => reading,
+            Err(err) => {
+                log::warn!("{err}");
+                return;
+            }
+        };
+        let name = &self.config.name;
+        log::trace!("Command `{name}` reported {reading:?}");
+        match reading {
+            Reading::Sensor(target, value) => space.set_sensor(target, value).await,
+            Reading::State(open) => {
+                let source = format!("Command `{name}`");
+                self.requested.request(space, open, &source).await;
+            }
+        }
+    }
+
+    /// Starts the task running the command for `space` right away and every interval
+    ///
+    /// A command still running when the task is stopped is killed.
+    pub fn start(&self, space: SpaceGuard) -> Runner {
+        let instance = self.clone();
+        Runner::start(
+            format!("Command `{}`", self.config.name),
+            self.clock.clone(),
+            self.config.interval,
+            move || {
+                let instance = instance.clone();
+                let space = space.clone();
+                async move { instance.update(&space).await }
+            },
+        )
+    }
+}
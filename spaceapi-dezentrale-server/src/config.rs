@@ -1,8 +1,12 @@
+use crate::{
+    calendar::Calendar, closing::ClosingSoon, cors::Cors, display::StatusDisplays, history::StateChange,
+    sensors::SensorTarget, state::SpaceState,
+};
 use rand::RngCore;
-use rocket::serde::{de::Error, Deserialize, Deserializer, Serialize};
+use rocket::serde::{de::Error, json::Value, Deserialize, Deserializer, Serialize};
 use std::{io::Read, time::Duration};
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct ApiKey(pub String);
 
 impl ApiKey {
@@ -26,6 +30,88 @@ impl From<&str> for ApiKey {
     }
 }
 
+/// Bounds of the duration a client can request for a keep open lease
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KeepOpenLimits {
+    #[serde(
+        default,
+        rename = "min",
+        deserialize_with = "deserialize_optional_duration_secs_from_string"
+    )]
+    pub min: Option<Duration>,
+    #[serde(
+        default,
+        rename = "max",
+        deserialize_with = "deserialize_optional_duration_secs_from_string"
+    )]
+    pub max: Option<Duration>,
+}
+
+impl KeepOpenLimits {
+    /// Limits of `self`, the unset ones are taken from `defaults`
+    pub fn or(self, defaults: KeepOpenLimits) -> Self {
+        KeepOpenLimits {
+            min: self.min.or(defaults.min),
+            max: self.max.or(defaults.max),
+        }
+    }
+
+    /// Bounds the requested duration
+    pub fn bound(&self, requested: Duration) -> Duration {
+        let max = self.max.unwrap_or(Duration::MAX);
+        let min = self.min.unwrap_or_default().min(max);
+        requested.clamp(min, max)
+    }
+
+    fn validate(&self, name: &str) -> Result<(), String> {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min > max => Err(format!(
+                "Keep open limits of `{name}` have a minimum above the maximum"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What an API key may access
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum KeyScope {
+    /// All admin routes, which change the state
+    #[default]
+    #[serde(rename = "admin")]
+    Admin,
+    /// Only the members view, e.g. for members reading the detailed status
+    #[serde(rename = "read")]
+    Read,
+}
+
+/// API key with a name to tell the clients apart
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NamedApiKey {
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "key")]
+    pub key: ApiKey,
+    #[serde(default, rename = "scope")]
+    pub scope: KeyScope,
+    /// Limits of the keep open duration of this key, the limits of `admin` otherwise
+    #[serde(default, rename = "keep_open")]
+    pub keep_open: KeepOpenLimits,
+}
+
+/// Name of the key configured with `admin.api_key`
+pub const DEFAULT_API_KEY_NAME: &str = "default";
+
+/// All API keys which are allowed to access the admin routes or the members view
+#[derive(Debug, Default)]
+pub struct ApiKeys(pub Vec<NamedApiKey>);
+
+impl ApiKeys {
+    pub fn find(&self, key: &str) -> Option<&NamedApiKey> {
+        self.0.iter().find(|named| named.key.0 == key)
+    }
+}
+
 fn deserialize_duration_secs_from_string<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
@@ -36,28 +122,257 @@ where
     Ok(Duration::from_secs(value))
 }
 
-fn deserialize_duration_millis_from_string<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+fn deserialize_optional_duration_secs_from_string<'de, D>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let value = String::deserialize(deserializer)?
-        .parse::<u64>()
-        .map_err(|err| D::Error::custom(format!("{err}")))?;
-    Ok(Duration::from_millis(value))
+    deserialize_duration_secs_from_string(deserializer).map(Some)
+}
+
+fn deserialize_weekday_from_string<'de, D>(deserializer: D) -> Result<chrono::Weekday, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse::<chrono::Weekday>()
+        .map_err(|err| D::Error::custom(format!("{err}")))
+}
+
+fn deserialize_time_from_string<'de, D>(deserializer: D) -> Result<chrono::NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    chrono::NaiveTime::parse_from_str(&value, "%H:%M").map_err(|err| D::Error::custom(format!("{err}")))
 }
 
 fn default_keep_open_interval() -> Duration {
     Duration::from_secs(300)
 }
 
-fn default_tick_interval() -> Duration {
-    Duration::from_millis(100)
+fn default_rate_limit_window() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_rate_limit_per_ip() -> u32 {
+    60
+}
+
+fn default_rate_limit_global() -> u32 {
+    600
+}
+
+fn default_rate_limit_max_failures() -> u32 {
+    10
+}
+
+fn default_rate_limit_lockout() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// Limits for requests to the admin routes, disabled by default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+    /// Time window in which the requests are counted
+    #[serde(
+        default = "default_rate_limit_window",
+        rename = "window",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub window: Duration,
+    /// Maximum requests per window of a single IP address
+    #[serde(default = "default_rate_limit_per_ip", rename = "per_ip")]
+    pub per_ip: u32,
+    /// Maximum requests per window of all clients
+    #[serde(default = "default_rate_limit_global", rename = "global")]
+    pub global: u32,
+    /// Failed API key attempts per window of an IP address till it is locked out
+    #[serde(default = "default_rate_limit_max_failures", rename = "max_failures")]
+    pub max_failures: u32,
+    /// Duration of a lockout
+    #[serde(
+        default = "default_rate_limit_lockout",
+        rename = "lockout",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub lockout: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: false,
+            window: default_rate_limit_window(),
+            per_ip: default_rate_limit_per_ip(),
+            global: default_rate_limit_global(),
+            max_failures: default_rate_limit_max_failures(),
+            lockout: default_rate_limit_lockout(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && self.max_failures == 0 {
+            return Err("Rate limit needs `max_failures` of at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn default_heartbeat_timeout() -> Duration {
+    Duration::from_secs(600)
+}
+
+fn default_heartbeat_message() -> String {
+    "The door controller doesn't respond".to_string()
+}
+
+/// Status published when the heartbeat or a state source times out
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaleStatus {
+    /// Published as `open: null`
+    #[default]
+    #[serde(rename = "unknown")]
+    Unknown,
+    #[serde(rename = "closed")]
+    Closed,
+}
+
+impl StaleStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StaleStatus::Unknown => "unknown",
+            StaleStatus::Closed => "closed",
+        }
+    }
+}
+
+/// Heartbeat of the devices changing the state, e.g. the door controller
+///
+/// If none of the designated API keys sent an authenticated request within the timeout, the
+/// space is published with the stale status. The heartbeat is disabled without sources.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatConfig {
+    /// Names of the API keys whose requests count as heartbeat
+    #[serde(default, rename = "sources")]
+    pub sources: Vec<String>,
+    #[serde(
+        default = "default_heartbeat_timeout",
+        rename = "timeout",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub timeout: Duration,
+    #[serde(default, rename = "status")]
+    pub status: StaleStatus,
+    /// Message published with the stale status
+    #[serde(default = "default_heartbeat_message", rename = "message")]
+    pub message: String,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            sources: vec![],
+            timeout: default_heartbeat_timeout(),
+            status: StaleStatus::default(),
+            message: default_heartbeat_message(),
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.sources.is_empty()
+    }
+}
+
+fn default_closing_soon_message() -> String {
+    "Closing in {{ minutes }} minutes".to_string()
+}
+
+/// Warning before a keep open request expires, so people on site can extend it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClosingSoonConfig {
+    /// Time before the end of a keep open request the warning starts, disabled with zero
+    #[serde(
+        default,
+        rename = "period",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub period: Duration,
+    /// Published message while the warning lasts, a template with the value `minutes`
+    #[serde(default = "default_closing_soon_message", rename = "message")]
+    pub message: String,
+    /// URL which is notified with a POST request when the warning starts
+    #[serde(default, rename = "webhook")]
+    pub webhook: Option<String>,
+    /// Time the webhook may take to answer
+    #[serde(
+        default = "default_webhook_timeout",
+        rename = "webhook_timeout",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub webhook_timeout: Duration,
+}
+
+impl Default for ClosingSoonConfig {
+    fn default() -> Self {
+        ClosingSoonConfig {
+            period: Duration::ZERO,
+            message: default_closing_soon_message(),
+            webhook: None,
+            webhook_timeout: default_webhook_timeout(),
+        }
+    }
+}
+
+fn default_webhook_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_check_in_timeout() -> Duration {
+    Duration::from_secs(12 * 3600)
+}
+
+/// Check-in of members on site, published as `people_now_present` sensor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckInConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+    /// Time after which members are checked out automatically, unless they check in again
+    #[serde(
+        default = "default_check_in_timeout",
+        rename = "timeout",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub timeout: Duration,
+    /// Opens the space on the first check-in and closes it on the last check-out
+    #[serde(default, rename = "auto_open")]
+    pub auto_open: bool,
+}
+
+impl Default for CheckInConfig {
+    fn default() -> Self {
+        CheckInConfig {
+            enabled: false,
+            timeout: default_check_in_timeout(),
+            auto_open: false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AdminConfig {
     #[serde(default, rename = "api_key")]
     pub api_key: Option<ApiKey>,
+    /// Additional keys with names, e.g. one per device
+    #[serde(default, rename = "api_keys")]
+    pub api_keys: Vec<NamedApiKey>,
     #[serde(default, rename = "enable")]
     pub enabled: bool,
     #[serde(
@@ -66,22 +381,73 @@ pub struct AdminConfig {
         deserialize_with = "deserialize_duration_secs_from_string"
     )]
     pub keep_open_interval: Duration,
+    /// Limits of the requested keep open durations, the maximum is the keep open interval by default
+    #[serde(default, rename = "keep_open_limits")]
+    pub keep_open_limits: KeepOpenLimits,
+    #[serde(default, rename = "closing_soon")]
+    pub closing_soon: ClosingSoonConfig,
+    /// Time an open or close request has to be stable till it's published, disabled with zero
     #[serde(
-        default = "default_tick_interval",
-        rename = "tick_interval",
-        deserialize_with = "deserialize_duration_millis_from_string"
+        default,
+        rename = "debounce",
+        deserialize_with = "deserialize_duration_secs_from_string"
     )]
-    pub tick_interval: Duration,
+    pub debounce: Duration,
+    #[serde(default, rename = "rate_limit")]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default, rename = "heartbeat")]
+    pub heartbeat: HeartbeatConfig,
+    #[serde(default, rename = "check_in")]
+    pub check_in: CheckInConfig,
 }
 
 impl Default for AdminConfig {
     fn default() -> Self {
         AdminConfig {
             api_key: None,
+            api_keys: vec![],
             enabled: false,
             keep_open_interval: Duration::from_secs(300),
-            tick_interval: Duration::from_millis(100),
+            keep_open_limits: KeepOpenLimits::default(),
+            closing_soon: ClosingSoonConfig::default(),
+            debounce: Duration::ZERO,
+            rate_limit: RateLimitConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            check_in: CheckInConfig::default(),
+        }
+    }
+}
+
+impl AdminConfig {
+    /// Collects the named keys and the key of `api_key`, generates one if no key is set
+    ///
+    /// The keep open limits of the keys are completed with the ones of the admin section.
+    pub fn keys(&self) -> ApiKeys {
+        let mut keys = self.api_keys.clone();
+        if let Some(key) = &self.api_key {
+            keys.push(NamedApiKey {
+                name: DEFAULT_API_KEY_NAME.to_string(),
+                key: key.clone(),
+                scope: KeyScope::Admin,
+                keep_open: KeepOpenLimits::default(),
+            });
+        }
+        if keys.is_empty() {
+            keys.push(NamedApiKey {
+                name: DEFAULT_API_KEY_NAME.to_string(),
+                key: ApiKey::generate(),
+                scope: KeyScope::Admin,
+                keep_open: KeepOpenLimits::default(),
+            });
         }
+        let defaults = self.keep_open_limits.or(KeepOpenLimits {
+            min: None,
+            max: Some(self.keep_open_interval),
+        });
+        for key in &mut keys {
+            key.keep_open = key.keep_open.or(defaults);
+        }
+        ApiKeys(keys)
     }
 }
 
@@ -93,12 +459,25 @@ fn default_status_display_closed() -> String {
     "closed".to_string()
 }
 
+/// Templates for displaying the status
+///
+/// The templates are rendered with [minijinja](https://docs.rs/minijinja), see
+/// [`DisplayContext`](crate::display::DisplayContext) for the available values.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StatusDisplay {
     #[serde(default = "default_status_display_open", rename = "open")]
     pub open: String,
     #[serde(default = "default_status_display_closed", rename = "closed")]
     pub closed: String,
+    /// Used if the state is unknown, the closed template otherwise
+    #[serde(default, rename = "unknown")]
+    pub unknown: Option<String>,
+    /// Used while the space is closed for maintenance, the closed template otherwise
+    #[serde(default, rename = "maintenance")]
+    pub maintenance: Option<String>,
+    /// Used while the space is closed for a private event, the closed template otherwise
+    #[serde(default, rename = "private_event")]
+    pub private_event: Option<String>,
 }
 
 impl Default for StatusDisplay {
@@ -106,6 +485,140 @@ impl Default for StatusDisplay {
         StatusDisplay {
             open: "open".to_string(),
             closed: "closed".to_string(),
+            unknown: None,
+            maintenance: None,
+            private_event: None,
+        }
+    }
+}
+
+/// RGB color written as `#rrggbb` or `#rgb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 3]);
+
+impl std::str::FromStr for Color {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, <Self as std::str::FromStr>::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.is_ascii())
+            .ok_or(format!("Invalid color `{s}`, expected `#rrggbb`"))?;
+        let channel =
+            |hex: &str| u8::from_str_radix(hex, 16).map_err(|err| format!("Invalid color `{s}`: {err}"));
+        match hex.len() {
+            3 => {
+                let mut color = [0; 3];
+                for (idx, value) in color.iter_mut().enumerate() {
+                    *value = channel(&hex[idx..idx + 1])? * 0x11;
+                }
+                Ok(Color(color))
+            }
+            6 => Ok(Color([
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            ])),
+            _ => Err(format!("Invalid color `{s}`, expected `#rrggbb`")),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        let [r, g, b] = color.0;
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+/// Text and color of one side of a badge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BadgePart {
+    #[serde(rename = "text")]
+    pub text: String,
+    #[serde(rename = "color")]
+    pub color: Color,
+}
+
+fn default_badge_label() -> BadgePart {
+    BadgePart {
+        text: "space".to_string(),
+        color: Color([0x55, 0x55, 0x55]),
+    }
+}
+
+fn default_badge_open() -> BadgePart {
+    BadgePart {
+        text: "open".to_string(),
+        color: Color([0x44, 0xcc, 0x11]),
+    }
+}
+
+fn default_badge_closed() -> BadgePart {
+    BadgePart {
+        text: "closed".to_string(),
+        color: Color([0xe0, 0x5d, 0x44]),
+    }
+}
+
+fn default_badge_unknown() -> BadgePart {
+    BadgePart {
+        text: "unknown".to_string(),
+        color: Color([0x9f, 0x9f, 0x9f]),
+    }
+}
+
+fn default_badge_maintenance() -> BadgePart {
+    BadgePart {
+        text: "maintenance".to_string(),
+        color: Color([0xdf, 0xb3, 0x17]),
+    }
+}
+
+fn default_badge_private_event() -> BadgePart {
+    BadgePart {
+        text: "private event".to_string(),
+        color: Color([0xfe, 0x7d, 0x37]),
+    }
+}
+
+/// Badge shown on `/status/badge.svg` and `/status/badge.png`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BadgeDisplay {
+    #[serde(default = "default_badge_label", rename = "label")]
+    pub label: BadgePart,
+    #[serde(default = "default_badge_open", rename = "open")]
+    pub open: BadgePart,
+    /// Used while a keep open request keeps the space open, the open part otherwise
+    #[serde(default, rename = "keep_open")]
+    pub keep_open: Option<BadgePart>,
+    #[serde(default = "default_badge_closed", rename = "closed")]
+    pub closed: BadgePart,
+    #[serde(default = "default_badge_unknown", rename = "unknown")]
+    pub unknown: BadgePart,
+    #[serde(default = "default_badge_maintenance", rename = "maintenance")]
+    pub maintenance: BadgePart,
+    #[serde(default = "default_badge_private_event", rename = "private_event")]
+    pub private_event: BadgePart,
+}
+
+impl Default for BadgeDisplay {
+    fn default() -> Self {
+        BadgeDisplay {
+            label: default_badge_label(),
+            open: default_badge_open(),
+            keep_open: None,
+            closed: default_badge_closed(),
+            unknown: default_badge_unknown(),
+            maintenance: default_badge_maintenance(),
+            private_event: default_badge_private_event(),
         }
     }
 }
@@ -116,16 +629,767 @@ pub struct StatusDisplayTypes {
     pub text: StatusDisplay,
     #[serde(default, rename = "html")]
     pub html: StatusDisplay,
+    #[serde(default, rename = "badge")]
+    pub badge: BadgeDisplay,
+}
+
+fn default_history_size() -> usize {
+    1000
+}
+
+/// Storage of the state changes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Amount of changes kept
+    #[serde(default = "default_history_size", rename = "size")]
+    pub size: usize,
+    /// File to persist the changes, kept in memory only if not set
+    #[serde(default, rename = "file")]
+    pub file: Option<std::path::PathBuf>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            size: default_history_size(),
+            file: None,
+        }
+    }
+}
+
+/// Weekly recurring opening slot, times are local times of the space
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleSlot {
+    #[serde(rename = "weekday", deserialize_with = "deserialize_weekday_from_string")]
+    pub weekday: chrono::Weekday,
+    #[serde(rename = "start", deserialize_with = "deserialize_time_from_string")]
+    pub start: chrono::NaiveTime,
+    /// End of the slot, an end before the start ends on the next day
+    #[serde(rename = "end", deserialize_with = "deserialize_time_from_string")]
+    pub end: chrono::NaiveTime,
+    #[serde(default, rename = "summary")]
+    pub summary: Option<String>,
+}
+
+fn default_calendar_weeks() -> u32 {
+    4
+}
+
+/// Calendar of opening times on `/calendar.ics`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+    /// Planned opening times
+    #[serde(default, rename = "schedule")]
+    pub schedule: Vec<ScheduleSlot>,
+    /// Amount of weeks planned slots are published in advance
+    #[serde(default = "default_calendar_weeks", rename = "weeks")]
+    pub weeks: u32,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        CalendarConfig {
+            enabled: false,
+            schedule: vec![],
+            weeks: default_calendar_weeks(),
+        }
+    }
+}
+
+fn default_feed_size() -> usize {
+    20
+}
+
+/// Atom feed of the state changes on `/feed.xml`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeedConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+    /// Amount of changes in the feed
+    #[serde(default = "default_feed_size", rename = "size")]
+    pub size: usize,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        FeedConfig {
+            enabled: false,
+            size: default_feed_size(),
+        }
+    }
+}
+
+/// Prometheus metrics on `/metrics`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+}
+
+fn default_cors_methods() -> Vec<String> {
+    vec!["GET".to_string(), "POST".to_string()]
+}
+
+fn default_cors_headers() -> Vec<String> {
+    vec!["X-API-Key".to_string()]
+}
+
+fn default_cors_max_age() -> Duration {
+    Duration::from_secs(600)
+}
+
+/// CORS policy for a group of routes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsPolicy {
+    /// Allowed origins, either exact (`https://example.org`), with a wildcard
+    /// (`https://*.example.org`) or `*` for all
+    #[serde(default, rename = "origins")]
+    pub origins: Vec<String>,
+    #[serde(default = "default_cors_methods", rename = "methods")]
+    pub methods: Vec<String>,
+    /// Request headers allowed in addition to the CORS-safelisted ones
+    #[serde(default = "default_cors_headers", rename = "headers")]
+    pub headers: Vec<String>,
+    #[serde(default, rename = "credentials")]
+    pub credentials: bool,
+    /// How long browsers may cache the result of a preflight request
+    #[serde(
+        default = "default_cors_max_age",
+        rename = "max_age",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub max_age: Duration,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        CorsPolicy {
+            origins: vec![],
+            methods: default_cors_methods(),
+            headers: default_cors_headers(),
+            credentials: false,
+            max_age: default_cors_max_age(),
+        }
+    }
+}
+
+fn default_cors_public() -> CorsPolicy {
+    CorsPolicy {
+        origins: vec!["*".to_string()],
+        ..CorsPolicy::default()
+    }
+}
+
+/// CORS policies of the public routes and of the admin routes below `/admin`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    #[serde(default = "default_cors_public", rename = "public")]
+    pub public: CorsPolicy,
+    #[serde(default, rename = "admin")]
+    pub admin: CorsPolicy,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            public: default_cors_public(),
+            admin: CorsPolicy::default(),
+        }
+    }
+}
+
+/// `Cache-Control` max-age of the status routes, with `0` clients have to revalidate every time
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CacheControlConfig {
+    /// Max-age of `/spaceapi/v14`
+    #[serde(
+        default,
+        rename = "spaceapi",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub spaceapi: Duration,
+    /// Max-age of `/status/text`
+    #[serde(
+        default,
+        rename = "text",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub text: Duration,
+    /// Max-age of `/status/html`
+    #[serde(
+        default,
+        rename = "html",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub html: Duration,
+    /// Max-age of `/status/json`
+    #[serde(
+        default,
+        rename = "json",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub json: Duration,
+}
+
+fn default_members_history() -> usize {
+    20
+}
+
+/// Detailed status for members on `/admin/members`, readable with API keys of any scope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MembersConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+    /// Amount of the latest state changes in the view
+    #[serde(default = "default_members_history", rename = "history")]
+    pub history: usize,
+}
+
+impl Default for MembersConfig {
+    fn default() -> Self {
+        MembersConfig {
+            enabled: false,
+            history: default_members_history(),
+        }
+    }
+}
+
+/// Fields removed from the public status routes, the feed and the calendar
+///
+/// The members view always contains all fields.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    /// Hides who changed the state
+    #[serde(default, rename = "hide_trigger_person")]
+    pub hide_trigger_person: bool,
+    /// Hides the messages of the state changes, a closing soon warning is still published
+    #[serde(default, rename = "hide_message")]
+    pub hide_message: bool,
+    /// Hides the sensors of `/spaceapi/v14` and of the status display templates
+    #[serde(default, rename = "hide_sensors")]
+    pub hide_sensors: bool,
+}
+
+impl PrivacyConfig {
+    /// Copy of a recorded change without the hidden fields
+    pub fn public_change(&self, change: &StateChange) -> StateChange {
+        StateChange {
+            timestamp: change.timestamp,
+            open: change.open,
+            trigger_person: change
+                .trigger_person
+                .clone()
+                .filter(|_| !self.hide_trigger_person),
+            message: change.message.clone().filter(|_| !self.hide_message),
+            published: change.published,
+        }
+    }
+}
+
+/// File listing the devices on the network
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkSource {
+    /// Leases file of dnsmasq
+    #[default]
+    #[serde(rename = "dnsmasq")]
+    Dnsmasq,
+    /// Leases file of the ISC DHCP server
+    #[serde(rename = "isc_dhcp")]
+    IscDhcp,
+    /// ARP table of the kernel
+    #[serde(rename = "arp")]
+    Arp,
+}
+
+impl NetworkSource {
+    /// Usual location of the file
+    pub fn default_path(&self) -> &'static str {
+        match self {
+            NetworkSource::Dnsmasq => "/var/lib/misc/dnsmasq.leases",
+            NetworkSource::IscDhcp => "/var/lib/dhcp/dhcpd.leases",
+            NetworkSource::Arp => "/proc/net/arp",
+        }
+    }
+}
+
+/// Device registered by a member
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownDevice {
+    /// Name of the member owning the device
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "mac")]
+    pub mac: String,
+    /// Whether the member agreed to publish the name while the device is present
+    #[serde(default, rename = "public")]
+    pub public: bool,
+}
+
+fn default_network_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_count_anonymous() -> bool {
+    true
+}
+
+/// Presence derived from the devices on the network, published as `people_now_present` sensor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkPresenceConfig {
+    #[serde(default, rename = "enable")]
+    pub enabled: bool,
+    #[serde(default, rename = "source")]
+    pub source: NetworkSource,
+    /// File to read, the usual location of the source by default
+    #[serde(default, rename = "path")]
+    pub path: Option<std::path::PathBuf>,
+    /// Time between two scans of the file
+    #[serde(
+        default = "default_network_interval",
+        rename = "interval",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub interval: Duration,
+    /// Devices of members, counted once per member
+    #[serde(default, rename = "devices")]
+    pub devices: Vec<KnownDevice>,
+    /// Devices which are never counted, e.g. printers and access points
+    #[serde(default, rename = "ignore")]
+    pub ignore: Vec<String>,
+    /// Counts every unknown device as one person
+    #[serde(default = "default_count_anonymous", rename = "count_anonymous")]
+    pub count_anonymous: bool,
+    /// Keeps the space open while counted devices are present
+    #[serde(default, rename = "keep_open")]
+    pub keep_open: bool,
+}
+
+impl Default for NetworkPresenceConfig {
+    fn default() -> Self {
+        NetworkPresenceConfig {
+            enabled: false,
+            source: NetworkSource::default(),
+            path: None,
+            interval: default_network_interval(),
+            devices: vec![],
+            ignore: vec![],
+            count_anonymous: default_count_anonymous(),
+            keep_open: false,
+        }
+    }
+}
+
+impl NetworkPresenceConfig {
+    /// File to read
+    pub fn path(&self) -> std::path::PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| self.source.default_path().into())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval.is_zero() {
+            return Err("The network presence interval must not be zero".to_string());
+        }
+        let macs = self.devices.iter().map(|device| &device.mac).chain(&self.ignore);
+        for mac in macs {
+            if crate::network::normalize_mac(mac).is_none() {
+                return Err(format!("Invalid MAC address `{mac}` in network presence"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_command_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_command_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_open_outputs() -> Vec<String> {
+    vec!["open".to_string(), "1".to_string(), "true".to_string()]
+}
+
+fn default_closed_outputs() -> Vec<String> {
+    vec!["closed".to_string(), "0".to_string(), "false".to_string()]
+}
+
+/// Mapping of the result of a command to open and close requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandStateConfig {
+    /// Uses the exit code instead of the output, `0` is open and `1` closed
+    #[serde(default, rename = "exit_code")]
+    pub exit_code: bool,
+    /// Outputs meaning open, compared without surrounding whitespace
+    #[serde(default = "default_open_outputs", rename = "open")]
+    pub open: Vec<String>,
+    /// Outputs meaning closed, compared without surrounding whitespace
+    #[serde(default = "default_closed_outputs", rename = "closed")]
+    pub closed: Vec<String>,
+}
+
+impl Default for CommandStateConfig {
+    fn default() -> Self {
+        CommandStateConfig {
+            exit_code: false,
+            open: default_open_outputs(),
+            closed: default_closed_outputs(),
+        }
+    }
+}
+
+/// Command run periodically, its result is a sensor value or the state of the space
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandConfig {
+    /// Name of the source in the logs
+    #[serde(rename = "name")]
+    pub name: String,
+    /// Program and its arguments, run without a shell
+    #[serde(rename = "command")]
+    pub command: Vec<String>,
+    #[serde(
+        default = "default_command_interval",
+        rename = "interval",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub interval: Duration,
+    /// Time after which the command is killed and the run fails
+    #[serde(
+        default = "default_command_timeout",
+        rename = "timeout",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub timeout: Duration,
+    /// Sensor whose value is the output of the command
+    #[serde(default, rename = "sensor")]
+    pub sensor: Option<SensorTarget>,
+    /// Opens and closes the space on changes of the result of the command
+    #[serde(default, rename = "state")]
+    pub state: Option<CommandStateConfig>,
+}
+
+impl CommandConfig {
+    /// Checks the command and its sensor, which has to be part of the published `template`
+    pub fn validate(&self, template: &spaceapi_dezentrale::Status) -> Result<(), String> {
+        if self.command.is_empty() {
+            return Err(format!("Command `{}` has no program", self.name));
+        }
+        if self.interval.is_zero() || self.timeout.is_zero() {
+            return Err(format!("Command `{}` needs an interval and a timeout", self.name));
+        }
+        match (&self.sensor, &self.state) {
+            (Some(sensor), None) => sensor
+                .validate(template.sensors.as_ref())
+                .map_err(|err| format!("Command `{}`: {err}", self.name)),
+            (None, Some(_)) => Ok(()),
+            _ => Err(format!(
+                "Command `{}` needs either a `sensor` or a `state`",
+                self.name
+            )),
+        }
+    }
+}
+
+fn default_poller_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_poller_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Mapping of a polled value to open and closed
+///
+/// Numbers between `closed_below` and `open_above` keep the state, so a flickering value doesn't
+/// toggle the space.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ThresholdConfig {
+    /// Values meaning open, e.g. `true` or `"ON"`
+    #[serde(default, rename = "open")]
+    pub open: Vec<Value>,
+    /// Values meaning closed
+    #[serde(default, rename = "closed")]
+    pub closed: Vec<Value>,
+    /// Numbers at or above mean open
+    #[serde(default, rename = "open_above")]
+    pub open_above: Option<f64>,
+    /// Numbers at or below mean closed
+    #[serde(default, rename = "closed_below")]
+    pub closed_below: Option<f64>,
+}
+
+impl ThresholdConfig {
+    /// Whether `value` means open or closed, `None` if it means neither
+    pub fn state(&self, value: &Value) -> Option<bool> {
+        if self.open.contains(value) {
+            return Some(true);
+        }
+        if self.closed.contains(value) {
+            return Some(false);
+        }
+        let number = value.as_f64()?;
+        if self.open_above.is_some_and(|open_above| number >= open_above) {
+            Some(true)
+        } else if self
+            .closed_below
+            .is_some_and(|closed_below| number <= closed_below)
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.open.is_empty()
+            && self.closed.is_empty()
+            && self.open_above.is_none()
+            && self.closed_below.is_none()
+        {
+            return Err("needs values or thresholds meaning open or closed".to_string());
+        }
+        match (self.open_above, self.closed_below) {
+            (Some(open_above), Some(closed_below)) if open_above <= closed_below => {
+                Err("`open_above` must be greater than `closed_below`".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Value extracted from the polled document by its JSON pointer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollValueConfig {
+    /// JSON pointer of the value, e.g. `/StatusSNS/ENERGY/Power`
+    #[serde(rename = "pointer")]
+    pub pointer: String,
+    /// Sensor whose value is the extracted value
+    #[serde(default, rename = "sensor")]
+    pub sensor: Option<SensorTarget>,
+    /// Opens and closes the space on changes of the state meant by the extracted value
+    #[serde(default, rename = "state")]
+    pub state: Option<ThresholdConfig>,
+}
+
+/// URL of a device fetched periodically, answering with a JSON document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollerConfig {
+    /// Name of the source in the logs
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "url")]
+    pub url: String,
+    #[serde(
+        default = "default_poller_interval",
+        rename = "interval",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub interval: Duration,
+    #[serde(
+        default = "default_poller_timeout",
+        rename = "timeout",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub timeout: Duration,
+    /// Time without a successful poll till the values are stale, disabled with zero
+    ///
+    /// The sensors publish the values of the template again while the poller is stale.
+    #[serde(
+        default,
+        rename = "stale_after",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub stale_after: Duration,
+    /// Status published when a poller with a state gets stale, the state is kept if not set
+    #[serde(default, rename = "stale_status")]
+    pub stale_status: Option<StaleStatus>,
+    #[serde(rename = "values")]
+    pub values: Vec<PollValueConfig>,
+}
+
+impl PollerConfig {
+    /// Checks the values and their sensors, which have to be part of the published `template`
+    pub fn validate(&self, template: &spaceapi_dezentrale::Status) -> Result<(), String> {
+        let name = &self.name;
+        if self.interval.is_zero() || self.timeout.is_zero() {
+            return Err(format!("Poller `{name}` needs an interval and a timeout"));
+        }
+        if self.values.is_empty() {
+            return Err(format!("Poller `{name}` has no values"));
+        }
+        for value in &self.values {
+            if !value.pointer.is_empty() && !value.pointer.starts_with('/') {
+                return Err(format!(
+                    "Poller `{name}` has the invalid JSON pointer `{}`",
+                    value.pointer
+                ));
+            }
+            match (&value.sensor, &value.state) {
+                (Some(sensor), None) => sensor.validate(template.sensors.as_ref()),
+                (None, Some(state)) => state.validate(),
+                _ => Err("needs either a `sensor` or a `state`".to_string()),
+            }
+            .map_err(|err| format!("Poller `{name}` value `{}`: {err}", value.pointer))?;
+        }
+        Ok(())
+    }
+}
+
+fn default_signature_header() -> String {
+    "X-Hub-Signature-256".to_string()
+}
+
+/// Change of the space requested by a matching rule of an incoming webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookAction {
+    #[serde(rename = "open")]
+    Open,
+    #[serde(rename = "close")]
+    Close,
+    /// Keeps the space open with a lease named like the webhook
+    #[serde(rename = "keep_open")]
+    KeepOpen,
+    /// Sets the matched value as value of the sensor
+    #[serde(rename = "sensor")]
+    Sensor,
+}
+
+/// Rule matching a value of the payload of an incoming webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookRule {
+    /// JSON pointer of the value, e.g. `/event/type`
+    #[serde(rename = "pointer")]
+    pub pointer: String,
+    /// Values the rule matches, any value at the pointer if empty
+    #[serde(default, rename = "matches")]
+    pub matches: Vec<Value>,
+    #[serde(rename = "action")]
+    pub action: WebhookAction,
+    /// Sensor of the `sensor` action
+    #[serde(default, rename = "sensor")]
+    pub sensor: Option<SensorTarget>,
+    /// Duration of the `keep_open` action, the keep open interval if zero
+    #[serde(
+        default,
+        rename = "duration",
+        deserialize_with = "deserialize_duration_secs_from_string"
+    )]
+    pub duration: Duration,
+    /// JSON pointer of the trigger person of the `open` and `close` actions
+    #[serde(default, rename = "trigger_person")]
+    pub trigger_person: Option<String>,
+    /// Message of the `open` and `close` actions
+    #[serde(default, rename = "message")]
+    pub message: Option<String>,
+}
+
+/// Endpoint `/admin/webhooks/<name>` translating the JSON payloads of other systems into changes of the
+/// space
+///
+/// Requests authenticate with the `token`, sent in the `X-Webhook-Token` header or as bearer
+/// token, or with an HMAC-SHA256 signature of the body with the `hmac_secret`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncomingWebhookConfig {
+    /// Name of the webhook in the path and the logs
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(default, rename = "token")]
+    pub token: Option<ApiKey>,
+    #[serde(default, rename = "hmac_secret")]
+    pub hmac_secret: Option<String>,
+    /// Header of the hex encoded signature, optionally prefixed with `sha256=`
+    #[serde(default = "default_signature_header", rename = "signature_header")]
+    pub signature_header: String,
+    /// Rules applied in order, every matching rule is applied
+    #[serde(rename = "rules")]
+    pub rules: Vec<WebhookRule>,
+}
+
+impl IncomingWebhookConfig {
+    /// Checks the authentication and the rules, sensors have to be part of the published `template`
+    pub fn validate(&self, template: &spaceapi_dezentrale::Status) -> Result<(), String> {
+        let name = &self.name;
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(format!(
+                "Incoming webhook `{name}` needs a name of letters, digits, `-` and `_`"
+            ));
+        }
+        if self.token.is_some() == self.hmac_secret.is_some() {
+            return Err(format!(
+                "Incoming webhook `{name}` needs either a `token` or an `hmac_secret`"
+            ));
+        }
+        if self.rules.is_empty() {
+            return Err(format!("Incoming webhook `{name}` has no rules"));
+        }
+        let is_pointer = |pointer: &str| pointer.is_empty() || pointer.starts_with('/');
+        for rule in &self.rules {
+            let pointer = &rule.pointer;
+            let invalid = [Some(pointer), rule.trigger_person.as_ref()]
+                .into_iter()
+                .flatten()
+                .find(|pointer| !is_pointer(pointer));
+            if let Some(invalid) = invalid {
+                return Err(format!(
+                    "Incoming webhook `{name}` has the invalid JSON pointer `{invalid}`"
+                ));
+            }
+            match (rule.action, &rule.sensor) {
+                (WebhookAction::Sensor, Some(sensor)) => sensor.validate(template.sensors.as_ref()),
+                (WebhookAction::Sensor, None) => Err("needs a `sensor`".to_string()),
+                (_, Some(_)) => Err("only the `sensor` action has a `sensor`".to_string()),
+                (_, None) => Ok(()),
+            }
+            .map_err(|err| format!("Incoming webhook `{name}` rule `{pointer}`: {err}"))?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SpaceConfig {
     #[serde(rename = "publish")]
     pub publish: spaceapi_dezentrale::Status,
+    /// Public URL of this server, used to advertise its feeds
+    #[serde(default, rename = "base_url")]
+    pub base_url: Option<String>,
     #[serde(default, rename = "admin")]
     pub admin: AdminConfig,
     #[serde(default, rename = "status_display")]
     pub status_display: StatusDisplayTypes,
+    #[serde(default, rename = "history")]
+    pub history: HistoryConfig,
+    #[serde(default, rename = "calendar")]
+    pub calendar: CalendarConfig,
+    #[serde(default, rename = "feed")]
+    pub feed: FeedConfig,
+    #[serde(default, rename = "metrics")]
+    pub metrics: MetricsConfig,
+    #[serde(default, rename = "cors")]
+    pub cors: CorsConfig,
+    #[serde(default, rename = "cache_control")]
+    pub cache_control: CacheControlConfig,
+    #[serde(default, rename = "members")]
+    pub members: MembersConfig,
+    #[serde(default, rename = "privacy")]
+    pub privacy: PrivacyConfig,
+    #[serde(default, rename = "network_presence")]
+    pub network_presence: NetworkPresenceConfig,
+    #[serde(default, rename = "commands")]
+    pub commands: Vec<CommandConfig>,
+    #[serde(default, rename = "pollers")]
+    pub pollers: Vec<PollerConfig>,
+    #[serde(default, rename = "incoming_webhooks")]
+    pub incoming_webhooks: Vec<IncomingWebhookConfig>,
 }
 
 impl SpaceConfig {
@@ -144,13 +1408,59 @@ impl SpaceConfig {
         // Clear state
         config.publish.state = None;
 
-        if config.admin.api_key.is_none() {
+        StatusDisplays::new(&config.status_display)?
+            .validate(&SpaceState::new(config.admin.keep_open_interval), &config.publish)?;
+        Calendar::new(&config.calendar, &config.publish, &config.privacy)?;
+        Cors::new(&config.cors)?;
+        ClosingSoon::new(&config.admin.closing_soon)?;
+        config.admin.rate_limit.validate()?;
+        config.network_presence.validate()?;
+        for command in &config.commands {
+            command.validate(&config.publish)?;
+        }
+        for poller in &config.pollers {
+            poller.validate(&config.publish)?;
+        }
+        for (index, webhook) in config.incoming_webhooks.iter().enumerate() {
+            webhook.validate(&config.publish)?;
+            if config.incoming_webhooks[..index]
+                .iter()
+                .any(|other| other.name == webhook.name)
+            {
+                return Err(format!("Incoming webhook `{}` is configured twice", webhook.name));
+            }
+        }
+
+        if config.admin.api_key.is_none() && config.admin.api_keys.is_empty() {
             let key = ApiKey::generate();
             if config.admin.enabled {
                 log::warn!("API key isn't set. Generated a random one: {}", key.0);
             }
             config.admin.api_key = Some(key);
         }
+        let keys = config.admin.keys();
+        for key in &keys.0 {
+            key.keep_open.validate(&key.name)?;
+            // The network presence and incoming webhooks hold leases named after themselves
+            if key.name == crate::network::LEASE
+                || config
+                    .incoming_webhooks
+                    .iter()
+                    .any(|webhook| webhook.name == key.name)
+            {
+                return Err(format!(
+                    "API key `{}` is named like a lease owner of the server",
+                    key.name
+                ));
+            }
+        }
+        for source in &config.admin.heartbeat.sources {
+            if !keys.0.iter().any(|key| key.name == *source) {
+                return Err(format!(
+                    "Unknown heartbeat source `{source}`, expected the name of an API key"
+                ));
+            }
+        }
         Ok(config)
     }
 }
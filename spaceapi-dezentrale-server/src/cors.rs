@@ -0,0 +1,261 @@
+use crate::config::{CorsConfig, CorsPolicy};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{
+        hyper::header::{
+            ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        },
+        Header, Method, Status,
+    },
+    Request, Response,
+};
+use std::io::Cursor;
+
+/// Allowed origin of a CORS policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Origin {
+    Any,
+    Exact(String),
+    /// Origin with a wildcard, e.g. `https://*.example.org`
+    Wildcard {
+        prefix: String,
+        suffix: String,
+    },
+}
+
+impl Origin {
+    fn parse(origin: &str) -> Result<Self, String> {
+        let origin = origin.trim_end_matches('/');
+        if origin == "*" {
+            return Ok(Origin::Any);
+        }
+        match origin.split_once('*') {
+            None => Ok(Origin::Exact(origin.to_ascii_lowercase())),
+            Some((_, suffix)) if suffix.contains('*') => {
+                Err(format!("Origin `{origin}` contains more than one wildcard"))
+            }
+            Some((prefix, suffix)) => Ok(Origin::Wildcard {
+                prefix: prefix.to_ascii_lowercase(),
+                suffix: suffix.to_ascii_lowercase(),
+            }),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            Origin::Any => true,
+            Origin::Exact(exact) => *exact == origin,
+            // The wildcard has to match at least one character
+            Origin::Wildcard { prefix, suffix } => {
+                origin.len() > prefix.len() + suffix.len()
+                    && origin.starts_with(prefix.as_str())
+                    && origin.ends_with(suffix.as_str())
+            }
+        }
+    }
+}
+
+/// Validated CORS policy
+#[derive(Debug)]
+struct Policy {
+    origins: Vec<Origin>,
+    methods: Vec<Method>,
+    headers: Vec<String>,
+    credentials: bool,
+    max_age: u64,
+}
+
+impl Policy {
+    fn new(config: &CorsPolicy) -> Result<Self, String> {
+        let origins = config
+            .origins
+            .iter()
+            .map(|origin| Origin::parse(origin))
+            .collect::<Result<_, _>>()?;
+        let methods = config
+            .methods
+            .iter()
+            .map(|method| {
+                method
+                    .to_ascii_uppercase()
+                    .parse::<Method>()
+                    .map_err(|_| format!("Invalid CORS method `{method}`"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Policy {
+            origins,
+            methods,
+            headers: config.headers.clone(),
+            credentials: config.credentials,
+            max_age: config.max_age.as_secs(),
+        })
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|allowed| allowed.matches(origin))
+    }
+
+    fn allows_headers(&self, headers: &str) -> bool {
+        headers
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .all(|header| {
+                self.headers
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(header))
+            })
+    }
+
+    /// Value of `Access-Control-Allow-Origin`, if the origin is allowed
+    ///
+    /// Credentials can't be used with `*`, so the origin is reflected in that case.
+    fn allow_origin(&self, origin: Option<&str>) -> Option<String> {
+        if !self.credentials && self.origins.contains(&Origin::Any) {
+            return Some("*".to_string());
+        }
+        origin
+            .filter(|origin| self.allows_origin(origin))
+            .map(str::to_string)
+    }
+}
+
+/// Whether a route with the method is mounted for the path
+fn has_route(request: &Request<'_>, method: Method) -> bool {
+    let path = request.uri().path();
+    let segments = path.segments().collect::<Vec<_>>();
+    request
+        .rocket()
+        .routes()
+        .filter(|route| route.method == method)
+        .any(|route| {
+            let mut route_segments = route.uri.path().split('/').filter(|segment| !segment.is_empty());
+            let mut segments = segments.iter();
+            loop {
+                match (route_segments.next(), segments.next()) {
+                    (Some(pattern), _) if pattern.starts_with('<') && pattern.ends_with("..>") => {
+                        return true
+                    }
+                    (Some(pattern), Some(_)) if pattern.starts_with('<') => {}
+                    (Some(pattern), Some(segment)) if pattern == *segment => {}
+                    (None, None) => return true,
+                    _ => return false,
+                }
+            }
+        })
+}
+
+/// Fairing applying the configured CORS policies
+///
+/// Routes below `/admin` use the admin policy, all others the public one. Preflight requests are
+/// answered with `204 No Content` if the policy allows them and with `403 Forbidden` otherwise.
+#[derive(Debug)]
+pub struct Cors {
+    public: Policy,
+    admin: Policy,
+}
+
+impl Cors {
+    pub fn new(config: &CorsConfig) -> Result<Self, String> {
+        Ok(Cors {
+            public: Policy::new(&config.public)
+                .map_err(|err| format!("Invalid public CORS policy: {err}"))?,
+            admin: Policy::new(&config.admin).map_err(|err| format!("Invalid admin CORS policy: {err}"))?,
+        })
+    }
+
+    fn policy(&self, request: &Request<'_>) -> &Policy {
+        let path = request.uri().path();
+        if path == "/admin" || path.starts_with("/admin/") {
+            &self.admin
+        } else {
+            &self.public
+        }
+    }
+
+    fn preflight(&self, request: &Request<'_>, response: &mut Response<'_>, method: &str) {
+        let policy = self.policy(request);
+        let origin = request.headers().get_one(ORIGIN.as_str());
+        let headers = request
+            .headers()
+            .get_one(ACCESS_CONTROL_REQUEST_HEADERS.as_str())
+            .unwrap_or_default();
+
+        response.set_sized_body(0, Cursor::new(""));
+        let Ok(method) = method.parse::<Method>() else {
+            response.set_status(Status::Forbidden);
+            return;
+        };
+        if !has_route(request, method) {
+            response.set_status(Status::NotFound);
+            return;
+        }
+        let allow_origin = policy.allow_origin(origin);
+        let allowed = origin.is_some_and(|origin| policy.allows_origin(origin))
+            && policy.methods.contains(&method)
+            && policy.allows_headers(headers);
+        let Some(allow_origin) = allow_origin.filter(|_| allowed) else {
+            response.set_status(Status::Forbidden);
+            return;
+        };
+
+        response.set_status(Status::NoContent);
+        self.allow(response, policy, allow_origin);
+        let methods = policy
+            .methods
+            .iter()
+            .map(|method| method.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_METHODS.as_str(), methods));
+        if !policy.headers.is_empty() {
+            response.set_header(Header::new(
+                ACCESS_CONTROL_ALLOW_HEADERS.as_str(),
+                policy.headers.join(","),
+            ));
+        }
+        response.set_header(Header::new(
+            ACCESS_CONTROL_MAX_AGE.as_str(),
+            policy.max_age.to_string(),
+        ));
+    }
+
+    fn allow(&self, response: &mut Response<'_>, policy: &Policy, allow_origin: String) {
+        if allow_origin != "*" {
+            // The response depends on the origin, so caches have to distinguish them
+            response.adjoin_header(Header::new(VARY.as_str(), "Origin"));
+        }
+        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), allow_origin));
+        if policy.credentials {
+            response.set_header(Header::new(ACCESS_CONTROL_ALLOW_CREDENTIALS.as_str(), "true"));
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS Settings",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if request.method() == Method::Options {
+            if let Some(method) = request.headers().get_one(ACCESS_CONTROL_REQUEST_METHOD.as_str()) {
+                self.preflight(request, response, method);
+                return;
+            }
+        }
+
+        let policy = self.policy(request);
+        let origin = request.headers().get_one(ORIGIN.as_str());
+        if let Some(allow_origin) = policy.allow_origin(origin) {
+            self.allow(response, policy, allow_origin);
+        }
+    }
+}
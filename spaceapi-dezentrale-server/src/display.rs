@@ -0,0 +1,165 @@
+use crate::{
+    config::{BadgeDisplay, StatusDisplayTypes},
+    state::{SpaceState, SpaceStatus},
+};
+use minijinja::{AutoEscape, Environment};
+use rocket::serde::Serialize;
+
+const STATES: [SpaceStatus; 5] = [
+    SpaceStatus::Open,
+    SpaceStatus::Closed,
+    SpaceStatus::Unknown,
+    SpaceStatus::Maintenance(None),
+    SpaceStatus::PrivateEvent(None),
+];
+
+/// Output format of a status display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayFormat {
+    Text,
+    Html,
+}
+
+impl DisplayFormat {
+    fn prefix(&self) -> &'static str {
+        match self {
+            DisplayFormat::Text => "text",
+            DisplayFormat::Html => "html",
+        }
+    }
+}
+
+/// Values available inside of status display templates
+#[derive(Debug, Serialize)]
+pub struct DisplayContext<'a> {
+    /// Whether the space is open
+    pub open: bool,
+    /// Name of the status: `open`, `keep_open`, `closed`, `unknown`, `maintenance` or
+    /// `private_event`
+    pub state: &'static str,
+    /// Timestamp (UTC) of the last change between open and closed
+    pub lastchange: u64,
+    /// Person who triggered the last change
+    pub trigger_person: Option<&'a str>,
+    /// Message attached to the current state, the closing soon warning while it lasts
+    pub message: Option<&'a str>,
+    /// Timestamp (UTC) till the space is kept open
+    pub keep_open_until: Option<u64>,
+    /// Timestamp (UTC) till the maintenance or private event lasts, if set
+    pub mode_until: Option<u64>,
+    /// Minutes till the space closes, while the closing soon warning lasts
+    pub closing_in: Option<u64>,
+    /// Sensors of the published status
+    pub sensors: Option<&'a spaceapi_dezentrale::sensors::Sensors>,
+    /// Published status template with the space metadata
+    pub space: &'a spaceapi_dezentrale::Status,
+}
+
+impl<'a> DisplayContext<'a> {
+    pub fn new(state: &'a SpaceState, template: &'a spaceapi_dezentrale::Status) -> Self {
+        DisplayContext {
+            open: state.is_open(),
+            state: state.status.name(),
+            lastchange: crate::unix_timestamp(state.lastchange),
+            trigger_person: state.trigger_person.as_deref(),
+            message: state.published_message(),
+            keep_open_until: state.keep_open_until().map(crate::unix_timestamp),
+            mode_until: state
+                .status
+                .until()
+                .filter(|_| state.status.is_mode())
+                .map(crate::unix_timestamp),
+            closing_in: state.closing_soon.as_ref().map(|warning| warning.minutes),
+            sensors: template.sensors.as_ref(),
+            space: template,
+        }
+    }
+}
+
+/// Compiled templates of the status displays
+#[derive(Clone)]
+pub struct StatusDisplays {
+    env: Environment<'static>,
+    badge: BadgeDisplay,
+}
+
+impl StatusDisplays {
+    /// Compiles the configured templates
+    pub fn new(config: &StatusDisplayTypes) -> Result<Self, String> {
+        let mut env = Environment::new();
+        env.set_auto_escape_callback(|name| {
+            if name.starts_with("html/") {
+                AutoEscape::Html
+            } else {
+                AutoEscape::None
+            }
+        });
+        env.add_filter("datetime", crate::format_datetime);
+
+        for (format, display) in [
+            (DisplayFormat::Text, &config.text),
+            (DisplayFormat::Html, &config.html),
+        ] {
+            for (state, source) in [
+                ("open", Some(&display.open)),
+                ("closed", Some(&display.closed)),
+                ("unknown", display.unknown.as_ref()),
+                ("maintenance", display.maintenance.as_ref()),
+                ("private_event", display.private_event.as_ref()),
+            ] {
+                let Some(source) = source else {
+                    continue;
+                };
+                let name = format!("{}/{state}", format.prefix());
+                env.add_template_owned(name.clone(), source.clone())
+                    .map_err(|err| format!("Invalid status display template `{name}`: {err}"))?;
+            }
+        }
+        Ok(StatusDisplays {
+            env,
+            badge: config.badge.clone(),
+        })
+    }
+
+    /// Renders the display of the given format for the current state
+    ///
+    /// States without an own template use the closed one.
+    pub fn render(&self, format: DisplayFormat, context: &DisplayContext<'_>) -> Result<String, String> {
+        let state = match context.state {
+            _ if context.open => "open",
+            state @ ("unknown" | "maintenance" | "private_event") => state,
+            _ => "closed",
+        };
+        let mut name = format!("{}/{state}", format.prefix());
+        let template = match self.env.get_template(&name) {
+            Ok(template) => template,
+            Err(_) => {
+                name = format!("{}/closed", format.prefix());
+                self.env
+                    .get_template(&name)
+                    .map_err(|err| format!("Missing status display template `{name}`: {err}"))?
+            }
+        };
+        template
+            .render(context)
+            .map_err(|err| format!("Can't render status display template `{name}`: {err}"))
+    }
+
+    /// Badge configuration
+    pub fn badge(&self) -> &BadgeDisplay {
+        &self.badge
+    }
+
+    /// Renders all templates once with the given state to detect errors early
+    pub fn validate(&self, state: &SpaceState, template: &spaceapi_dezentrale::Status) -> Result<(), String> {
+        let mut context = DisplayContext::new(state, template);
+        for status in STATES {
+            context.open = status.is_open();
+            context.state = status.name();
+            for format in [DisplayFormat::Text, DisplayFormat::Html] {
+                self.render(format, &context)?;
+            }
+        }
+        Ok(())
+    }
+}
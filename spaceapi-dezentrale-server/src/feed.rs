@@ -0,0 +1,90 @@
+use crate::{
+    config::{FeedConfig, PrivacyConfig},
+    escape_xml, format_datetime,
+    history::History,
+};
+use std::fmt::Write;
+
+/// Generator of the Atom feed with the latest state changes
+pub struct Feed {
+    size: usize,
+    space: String,
+    url: String,
+    feed_url: Option<String>,
+    privacy: PrivacyConfig,
+}
+
+impl Feed {
+    pub fn new(
+        config: &FeedConfig,
+        template: &spaceapi_dezentrale::Status,
+        privacy: &PrivacyConfig,
+        feed_url: Option<String>,
+    ) -> Self {
+        Feed {
+            size: config.size,
+            space: template.space.clone(),
+            url: template.url.clone(),
+            feed_url,
+            privacy: privacy.clone(),
+        }
+    }
+
+    /// Renders the latest changes of the history, the newest first, without the hidden fields
+    pub fn render(&self, history: &History) -> String {
+        let space = escape_xml(&self.space);
+        let id = escape_xml(self.feed_url.as_deref().unwrap_or(&self.url));
+        let updated = format_datetime(history.last().map(|change| change.timestamp).unwrap_or(0));
+
+        let mut feed = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>{space} status</title>
+  <author><name>{space}</name></author>
+  <id>{id}</id>
+  <updated>{updated}</updated>
+  <link href="{0}"/>
+  <generator uri="https://github.com/dezentrale/spaceapi-rs" version="{1}">{2}</generator>
+"#,
+            escape_xml(&self.url),
+            crate::VERSION,
+            crate::SOFTWARE,
+        );
+        if let Some(feed_url) = &self.feed_url {
+            let _ = writeln!(feed, r#"  <link rel="self" href="{}"/>"#, escape_xml(feed_url));
+        }
+
+        for change in history.published().rev().take(self.size) {
+            let change = self.privacy.public_change(change);
+            let title = if change.open {
+                format!("{space} is open")
+            } else {
+                format!("{space} is closed")
+            };
+            let state = if change.open { "open" } else { "closed" };
+            let _ = write!(
+                feed,
+                r#"  <entry>
+    <title>{title}</title>
+    <id>{id}#{state}-{0}</id>
+    <updated>{1}</updated>
+"#,
+                change.timestamp,
+                format_datetime(change.timestamp),
+            );
+            if let Some(person) = &change.trigger_person {
+                let _ = writeln!(feed, "    <author><name>{}</name></author>", escape_xml(person));
+            }
+            // Atom requires a content of entries without an alternate link
+            let content = change.message.as_deref().unwrap_or(&title);
+            let _ = writeln!(
+                feed,
+                r#"    <content type="text">{}</content>"#,
+                escape_xml(content)
+            );
+            feed.push_str("  </entry>\n");
+        }
+        feed.push_str("</feed>\n");
+        feed
+    }
+}
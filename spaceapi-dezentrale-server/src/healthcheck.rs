@@ -0,0 +1,50 @@
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+/// Address to probe the server configured by Rocket (`Rocket.toml`, `ROCKET_*` variables)
+///
+/// If the server listens on all interfaces the loopback address is used.
+pub fn local_address() -> Result<SocketAddr, String> {
+    let config = rocket::Config::figment()
+        .extract::<rocket::Config>()
+        .map_err(|err| format!("Invalid Rocket config: {err}"))?;
+    let address = match config.address {
+        IpAddr::V4(address) if address.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(address) if address.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        address => address,
+    };
+    Ok(SocketAddr::new(address, config.port))
+}
+
+/// Requests `path` via plain HTTP and succeeds if the server answers with `200 OK`
+///
+/// The container images don't contain any tools like `curl`, so the server binary probes itself.
+pub fn probe(address: SocketAddr, path: &str, timeout: Duration) -> Result<(), String> {
+    let mut stream = TcpStream::connect_timeout(&address, timeout)
+        .map_err(|err| format!("Can't connect to {address}: {err}"))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(timeout)))
+        .map_err(|err| format!("Can't set timeout: {err}"))?;
+    let request = format!(
+        "GET {path} HTTP/1.0\r\nHost: {address}\r\nUser-Agent: {}/{}\r\n\r\n",
+        crate::SOFTWARE,
+        crate::VERSION
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|err| format!("Can't send request: {err}"))?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|err| format!("Can't read response: {err}"))?;
+    let status = response.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(format!("Unhealthy response `{status}`")),
+    }
+}
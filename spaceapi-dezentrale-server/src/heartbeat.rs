@@ -0,0 +1,142 @@
+use crate::{clock::Clock, config::HeartbeatConfig, state::SpaceGuard};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+use tokio::{
+    sync::{broadcast, watch, Notify},
+    task::JoinHandle,
+};
+
+/// Amount of events kept for slow subscribers
+const EVENT_CAPACITY: usize = 16;
+
+/// Change of the heartbeat of the designated sources
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeartbeatEvent {
+    /// No designated source sent a request since `last_seen`
+    Stale { last_seen: SystemTime },
+    /// The designated source `source` sent a request after the heartbeat went stale
+    Recovered { source: String },
+}
+
+/// Task publishing the stale status when the heartbeat times out
+struct Monitor {
+    handle: JoinHandle<()>,
+    shutdown: Arc<Notify>,
+}
+
+/// Watches the authenticated requests of the designated API keys, see [`HeartbeatConfig`]
+#[derive(Clone)]
+pub struct Heartbeat {
+    config: Arc<HeartbeatConfig>,
+    clock: Arc<dyn Clock>,
+    /// Time of the latest request of a designated source
+    last_seen: watch::Sender<SystemTime>,
+    stale: Arc<AtomicBool>,
+    events: broadcast::Sender<HeartbeatEvent>,
+    monitor: Arc<Mutex<Option<Monitor>>>,
+}
+
+impl Heartbeat {
+    /// The first heartbeat is expected within the timeout after the start
+    pub fn new(config: HeartbeatConfig, clock: Arc<dyn Clock>) -> Self {
+        let (last_seen, _) = watch::channel(clock.now());
+        Heartbeat {
+            config: Arc::new(config),
+            clock,
+            last_seen,
+            stale: Arc::new(AtomicBool::new(false)),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            monitor: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Receiver of the staleness events
+    pub fn subscribe(&self) -> broadcast::Receiver<HeartbeatEvent> {
+        self.events.subscribe()
+    }
+
+    /// Whether the heartbeat timed out and no designated source sent a request since
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::SeqCst)
+    }
+
+    /// Records an authenticated request of the API key `name`, ignored unless it's a designated source
+    pub fn beat(&self, name: &str) {
+        if !self.config.sources.iter().any(|source| source == name) {
+            return;
+        }
+        // Cleared before the time is sent, so the monitor sees a fresh heartbeat when it wakes up
+        if self.stale.swap(false, Ordering::SeqCst) {
+            log::info!("Heartbeat of `{name}` recovered");
+            let _ = self.events.send(HeartbeatEvent::Recovered {
+                source: name.to_string(),
+            });
+        }
+        self.last_seen.send_replace(self.clock.now());
+    }
+
+    /// Publishes the stale status if the heartbeat timed out at `now`
+    async fn check(&self, space: &SpaceGuard, now: SystemTime) {
+        let last_seen = *self.last_seen.borrow();
+        if now < last_seen + self.config.timeout || self.stale.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        log::warn!(
+            "No heartbeat from {} since {}, publishing the space as {}",
+            self.config.sources.join(", "),
+            humantime::format_rfc3339_seconds(last_seen),
+            self.config.status.name()
+        );
+        let message = Some(self.config.message.clone());
+        match space.stale(self.config.status, message).await {
+            Ok(Some(mode)) => log::debug!("Space is set to {}, keeping it", mode.name()),
+            Ok(None) => {}
+            Err(err) => log::error!("Can't publish stale heartbeat: {err}"),
+        }
+        let _ = self.events.send(HeartbeatEvent::Stale { last_seen });
+    }
+
+    /// Starts the task publishing the stale status of `space` when the heartbeat times out
+    pub fn start_monitor(&self, space: SpaceGuard) {
+        let instance = self.clone();
+        let mut last_seen = self.last_seen.subscribe();
+        let shutdown = Arc::new(Notify::new());
+        let stop = shutdown.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let seen = *last_seen.borrow_and_update();
+                // A stale heartbeat only changes with the next request
+                let deadline = (!instance.is_stale()).then(|| seen + instance.config.timeout);
+                let expired = async {
+                    match deadline {
+                        Some(deadline) => instance.clock.sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = expired => instance.check(&space, instance.clock.now()).await,
+                    _ = last_seen.changed() => {}
+                    _ = stop.notified() => break,
+                }
+            }
+            log::debug!("Heartbeat monitor stopped");
+        });
+        *self.monitor.lock().unwrap() = Some(Monitor { handle, shutdown });
+    }
+
+    /// Stops the monitor and waits till it finished
+    pub async fn stop_monitor(&self) {
+        let monitor = self.monitor.lock().unwrap().take();
+        if let Some(monitor) = monitor {
+            monitor.shutdown.notify_one();
+            if let Err(err) = monitor.handle.await {
+                log::error!("Heartbeat monitor failed: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::config::HistoryConfig;
+use rocket::serde::{json::serde_json, Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc;
+
+/// Recorded change between open and closed
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StateChange {
+    /// Timestamp (UTC) of the change
+    pub timestamp: u64,
+    pub open: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_person: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Whether the change was published, false for raw requests held back by the debouncing
+    #[serde(default = "default_published", skip_serializing_if = "is_published")]
+    pub published: bool,
+}
+
+fn default_published() -> bool {
+    true
+}
+
+fn is_published(published: &bool) -> bool {
+    *published
+}
+
+/// Task appending the recorded changes to the history file, so no file is written while the
+/// state is locked
+#[derive(Debug)]
+struct Writer {
+    path: PathBuf,
+    changes: mpsc::UnboundedSender<StateChange>,
+    /// Error of the last append, `None` if it succeeded
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Writer {
+    fn start(path: PathBuf) -> Self {
+        let (changes, mut receiver) = mpsc::unbounded_channel::<StateChange>();
+        let error = Arc::new(Mutex::new(None));
+        let last_error = error.clone();
+        let file = path.clone();
+        tokio::spawn(async move {
+            while let Some(change) = receiver.recv().await {
+                // Changes recorded meanwhile are appended at once
+                let mut batch = vec![change];
+                while let Ok(change) = receiver.try_recv() {
+                    batch.push(change);
+                }
+                let path = file.clone();
+                let result = tokio::task::spawn_blocking(move || append_changes(&path, &batch))
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|result| result);
+                if let Err(err) = &result {
+                    log::error!("Can't append to history file `{}`: {err:?}", file.display());
+                }
+                *last_error.lock().unwrap() = result
+                    .err()
+                    .map(|err| format!("Can't write history file `{}`: {err}", file.display()));
+            }
+        });
+        Writer { path, changes, error }
+    }
+}
+
+/// Bounded list of the latest state changes
+///
+/// If a file is configured every change is appended as JSON line to it, so the history survives a restart.
+#[derive(Debug)]
+pub struct History {
+    changes: VecDeque<StateChange>,
+    size: usize,
+    writer: Option<Writer>,
+}
+
+impl History {
+    /// History which is only kept in memory
+    pub fn new(size: usize) -> Self {
+        History {
+            changes: VecDeque::with_capacity(size),
+            size,
+            writer: None,
+        }
+    }
+
+    /// Loads the history from the configured file and compacts the file to the configured size
+    ///
+    /// The compacted file replaces the previous one only once it's written completely. Has to be
+    /// called within a Tokio runtime, which appends the changes recorded later.
+    pub fn load(config: &HistoryConfig) -> Result<Self, String> {
+        let mut history = History::new(config.size);
+        let Some(path) = &config.file else {
+            return Ok(history);
+        };
+
+        match std::fs::File::open(path) {
+            Ok(file) => {
+                for line in std::io::BufReader::new(file).lines() {
+                    let line = line.map_err(|err| format!("Can't read history file: {err:?}"))?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let change = serde_json::from_str::<StateChange>(&line)
+                        .map_err(|err| format!("Can't parse history file: {err}"))?;
+                    history.push(change);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("Can't open history file: {err:?}")),
+        }
+
+        compact(path, history.changes.iter()).map_err(|err| format!("Can't write history file: {err:?}"))?;
+        history.writer = Some(Writer::start(path.clone()));
+        Ok(history)
+    }
+
+    /// Adds a change, it's appended to the history file in the background
+    pub fn record(&mut self, change: StateChange) {
+        if let Some(writer) = &self.writer {
+            if writer.changes.send(change.clone()).is_err() {
+                log::error!(
+                    "Can't append to history file `{}`: writer stopped",
+                    writer.path.display()
+                );
+            }
+        }
+        self.push(change);
+    }
+
+    fn push(&mut self, change: StateChange) {
+        if self.size == 0 {
+            return;
+        }
+        if self.changes.len() == self.size {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    /// Whether the last change could be appended to the history file, always true if no file is
+    /// used
+    ///
+    /// The file isn't probed, it was writable when it was loaded.
+    pub fn check_writable(&self) -> Result<(), String> {
+        match &self.writer {
+            Some(writer) => match writer.error.lock().unwrap().clone() {
+                Some(err) => Err(err),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Latest published change
+    pub fn last(&self) -> Option<&StateChange> {
+        self.published().next_back()
+    }
+
+    /// Iterates over the changes and raw requests from the oldest to the newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &StateChange> {
+        self.changes.iter()
+    }
+
+    /// Iterates over the published changes from the oldest to the newest
+    pub fn published(&self) -> impl DoubleEndedIterator<Item = &StateChange> {
+        self.changes.iter().filter(|change| change.published)
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(crate::config::HistoryConfig::default().size)
+    }
+}
+
+fn write_change(file: &mut std::fs::File, change: &StateChange) -> std::io::Result<()> {
+    let line = serde_json::to_string(change).map_err(std::io::Error::other)?;
+    writeln!(file, "{line}")
+}
+
+fn append_changes(path: &Path, changes: &[StateChange]) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().append(true).create(true).open(path)?;
+    for change in changes {
+        write_change(&mut file, change)?;
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` with the `changes`, by renaming a completely written temporary file
+fn compact<'a>(path: &Path, changes: impl Iterator<Item = &'a StateChange>) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = std::fs::File::create(&temporary)?;
+    for change in changes {
+        write_change(&mut file, change)?;
+    }
+    file.sync_all()?;
+    std::fs::rename(&temporary, path)
+}
@@ -0,0 +1,166 @@
+use crate::{
+    config::{IncomingWebhookConfig, WebhookAction},
+    sensors::SensorTarget,
+    state::{SpaceStatus, MAX_DURATION},
+};
+use rocket::{http::HeaderMap, serde::json::Value};
+use std::time::{Duration, SystemTime};
+
+/// Header of the token of an incoming webhook, alternatively sent as bearer token
+pub const TOKEN_HEADER: &str = "X-Webhook-Token";
+
+/// Change of the space requested by a matching rule
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebhookMatch {
+    Open {
+        trigger_person: Option<String>,
+        message: Option<String>,
+    },
+    Close {
+        trigger_person: Option<String>,
+        message: Option<String>,
+    },
+    /// Keeps the space open for the duration, the keep open interval if zero
+    KeepOpen(Duration),
+    Sensor(SensorTarget, Value),
+}
+
+impl WebhookMatch {
+    pub fn action(&self) -> WebhookAction {
+        match self {
+            WebhookMatch::Open { .. } => WebhookAction::Open,
+            WebhookMatch::Close { .. } => WebhookAction::Close,
+            WebhookMatch::KeepOpen(_) => WebhookAction::KeepOpen,
+            WebhookMatch::Sensor(..) => WebhookAction::Sensor,
+        }
+    }
+
+    /// Status requested at `now`, `None` for sensor values
+    pub fn status(&self, now: SystemTime) -> Option<SpaceStatus> {
+        match self {
+            WebhookMatch::Open { .. } => Some(SpaceStatus::Open),
+            WebhookMatch::Close { .. } => Some(SpaceStatus::Closed),
+            WebhookMatch::KeepOpen(duration) => {
+                Some(SpaceStatus::KeepOpen(now + (*duration).min(MAX_DURATION)))
+            }
+            WebhookMatch::Sensor(..) => None,
+        }
+    }
+}
+
+/// Whether the `sent` token is the `token`, compared in constant time through their HMACs
+fn token_matches(sent: &str, token: &str) -> bool {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, token.as_bytes());
+    let tag = ring::hmac::sign(&key, token.as_bytes());
+    ring::hmac::verify(&key, sent.as_bytes(), tag.as_ref()).is_ok()
+}
+
+/// Bytes of a hex encoded string, `None` if it's invalid
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Incoming webhook translating payloads into changes of the space, see [`IncomingWebhookConfig`]
+pub struct IncomingWebhook {
+    pub config: IncomingWebhookConfig,
+    /// Published sensors, to reject values which don't fit before they're published
+    template: Option<spaceapi_dezentrale::sensors::Sensors>,
+}
+
+impl IncomingWebhook {
+    pub fn new(config: IncomingWebhookConfig, template: &spaceapi_dezentrale::Status) -> Self {
+        IncomingWebhook {
+            config,
+            template: template.sensors.clone(),
+        }
+    }
+
+    /// Whether the request with the `headers` and the `body` carries the token or a valid signature
+    pub fn authenticate(&self, headers: &HeaderMap<'_>, body: &[u8]) -> bool {
+        if let Some(token) = &self.config.token {
+            let sent = headers.get_one(TOKEN_HEADER).or_else(|| {
+                headers
+                    .get_one("Authorization")
+                    .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            });
+            return sent.is_some_and(|sent| token_matches(sent.trim(), &token.0));
+        }
+        let Some(secret) = &self.config.hmac_secret else {
+            return false;
+        };
+        let Some(signature) = headers.get_one(&self.config.signature_header) else {
+            return false;
+        };
+        let signature = signature.trim();
+        let Some(signature) = decode_hex(signature.strip_prefix("sha256=").unwrap_or(signature)) else {
+            return false;
+        };
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+        ring::hmac::verify(&key, body, &signature).is_ok()
+    }
+
+    /// Changes requested by the rules matching `payload`, in the order of the rules
+    ///
+    /// Fails if a value for a sensor doesn't fit, before any change is applied.
+    pub fn evaluate(&self, payload: &Value) -> Result<Vec<WebhookMatch>, String> {
+        let name = &self.config.name;
+        let mut matches = vec![];
+        for rule in &self.config.rules {
+            let Some(value) = payload.pointer(&rule.pointer) else {
+                continue;
+            };
+            if !rule.matches.is_empty() && !rule.matches.contains(value) {
+                continue;
+            }
+            log::debug!(
+                "Incoming webhook `{name}` matched `{}` with `{value}`",
+                rule.pointer
+            );
+            let trigger_person = rule
+                .trigger_person
+                .as_ref()
+                .and_then(|pointer| payload.pointer(pointer))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let message = rule.message.clone();
+            matches.push(match rule.action {
+                WebhookAction::Open => WebhookMatch::Open {
+                    trigger_person,
+                    message,
+                },
+                WebhookAction::Close => WebhookMatch::Close {
+                    trigger_person,
+                    message,
+                },
+                WebhookAction::KeepOpen => WebhookMatch::KeepOpen(rule.duration),
+                WebhookAction::Sensor => {
+                    let target = rule
+                        .sensor
+                        .clone()
+                        .ok_or_else(|| format!("Incoming webhook `{name}` has a rule without a sensor"))?;
+                    let mut published = self.template.clone();
+                    target
+                        .apply(&mut published, value)
+                        .map_err(|err| format!("Incoming webhook `{name}`: {err}"))?;
+                    WebhookMatch::Sensor(target, value.clone())
+                }
+            });
+        }
+        Ok(matches)
+    }
+}
+
+/// All incoming webhooks of the server
+pub struct IncomingWebhooks(pub Vec<IncomingWebhook>);
+
+impl IncomingWebhooks {
+    pub fn find(&self, name: &str) -> Option<&IncomingWebhook> {
+        self.0.iter().find(|webhook| webhook.config.name == name)
+    }
+}
@@ -1,8 +1,38 @@
-use spaceapi_dezentrale_server::{config::SpaceConfig, serve};
+use clap::{Arg, Command};
+use spaceapi_dezentrale_server::{config::SpaceConfig, healthcheck, serve};
+use std::time::Duration;
 
 #[rocket::main]
 async fn main() {
     env_logger::init();
+    let args = Command::new(std::env!("CARGO_PKG_NAME"))
+        .version(std::env!("CARGO_PKG_VERSION"))
+        .about("SpaceAPI server")
+        .subcommand(
+            Command::new("healthcheck")
+                .about("Probes the running server, exits with 0 if it is ready")
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .default_value("/readyz")
+                        .help("Path to probe"),
+                ),
+        )
+        .get_matches();
+
+    if let Some(args) = args.subcommand_matches("healthcheck") {
+        let path = args.get_one::<String>("path").expect("Default path");
+        let result = healthcheck::local_address()
+            .and_then(|address| healthcheck::probe(address, path, Duration::from_secs(5)));
+        match result {
+            Ok(()) => std::process::exit(0),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+    }
+
     let config_file = std::env::var("CONFIG_FILE").unwrap_or("config.yml".to_string());
     let config = SpaceConfig::load(config_file).expect("Invalid config");
     let _ = serve(config).await.launch().await.expect("Can't start server");
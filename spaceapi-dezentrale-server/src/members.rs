@@ -0,0 +1,171 @@
+use crate::{
+    escape_xml, format_datetime, history::StateChange, presence::Presence, routes::LeaseResponse,
+    state::SpaceState,
+};
+use rocket::serde::{
+    json::{serde_json, Value},
+    Deserialize, Serialize,
+};
+use std::{fmt::Write, time::SystemTime};
+
+/// Detailed status for members, contains the fields hidden from the public status routes
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MemberStatus {
+    /// Whether the space is open, `None` if unknown
+    pub open: Option<bool>,
+    /// Name of the status, see [`StatusLite`](crate::routes::StatusLite)
+    pub state: String,
+    /// Timestamp (UTC) of the last change between open and closed
+    pub since: u64,
+    /// Timestamp (UTC) till the space stays open, if kept open, or till the mode lasts, if set
+    pub until: Option<u64>,
+    /// Person who triggered the last change
+    pub trigger_person: Option<String>,
+    /// Message attached to the current state
+    pub message: Option<String>,
+    /// Leases keeping the space open
+    pub leases: Vec<LeaseResponse>,
+    /// Members checked in or with a device on the network, including those who didn't agree to
+    /// publish their names
+    pub present: Vec<String>,
+    pub sensors: Option<spaceapi_dezentrale::sensors::Sensors>,
+    /// Latest changes between open and closed, the newest first, including the requests held back
+    /// by the debouncing
+    pub history: Vec<StateChange>,
+}
+
+impl MemberStatus {
+    /// Status of `space` at `now` with the latest `history` changes
+    pub fn new(
+        space: &SpaceState,
+        template: &spaceapi_dezentrale::Status,
+        now: SystemTime,
+        history: usize,
+    ) -> Self {
+        MemberStatus {
+            open: space.status.open(),
+            state: space.status.name().to_string(),
+            since: crate::unix_timestamp(space.lastchange),
+            until: space.status.until().map(crate::unix_timestamp),
+            trigger_person: space.trigger_person.clone(),
+            message: space.published_message().map(str::to_string),
+            leases: space
+                .active_leases(now)
+                .map(|lease| LeaseResponse {
+                    id: lease.id.clone(),
+                    key: lease.key.clone(),
+                    until: crate::unix_timestamp(lease.until),
+                })
+                .collect(),
+            present: space.presence.as_ref().map(Presence::names).unwrap_or_default(),
+            sensors: space.sensors(template),
+            history: space.history.iter().rev().take(history).cloned().collect(),
+        }
+    }
+
+    /// Sensors as lines of text, every sensor with a `value`
+    fn sensor_lines(&self) -> Vec<String> {
+        let sensors = self
+            .sensors
+            .as_ref()
+            .and_then(|sensors| serde_json::to_value(sensors).ok());
+        let Some(Value::Object(sensors)) = sensors else {
+            return vec![];
+        };
+        let mut lines = vec![];
+        for (kind, entries) in sensors {
+            let Value::Array(entries) = entries else {
+                continue;
+            };
+            for entry in entries {
+                let Some(value) = entry.get("value") else {
+                    continue;
+                };
+                let label = |name: &str| entry.get(name).and_then(Value::as_str).map(str::to_string);
+                let mut line = kind.clone();
+                for name in [label("name"), label("location")].into_iter().flatten() {
+                    let _ = write!(line, " {name}");
+                }
+                let _ = write!(line, ": {value}");
+                if let Some(unit) = label("unit") {
+                    let _ = write!(line, " {unit}");
+                }
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Minimalistic HTML page of the status
+    pub fn render_html(&self, space: &str) -> String {
+        let space = escape_xml(space);
+        let mut html = format!(
+            "<html>\n<head><title>{space} members</title></head>\n<body>\n<h1>{space} is {}</h1>\n<ul>\n",
+            escape_xml(&self.state)
+        );
+        let _ = writeln!(html, "<li>Since {}</li>", format_datetime(self.since));
+        if let Some(until) = self.until {
+            let _ = writeln!(html, "<li>Until {}</li>", format_datetime(until));
+        }
+        if let Some(person) = &self.trigger_person {
+            let _ = writeln!(html, "<li>By {}</li>", escape_xml(person));
+        }
+        if let Some(message) = &self.message {
+            let _ = writeln!(html, "<li>{}</li>", escape_xml(message));
+        }
+        html.push_str("</ul>\n");
+
+        if !self.leases.is_empty() {
+            html.push_str("<h2>Leases</h2>\n<ul>\n");
+            for lease in &self.leases {
+                let _ = writeln!(
+                    html,
+                    "<li>{} ({}) till {}</li>",
+                    escape_xml(&lease.id),
+                    escape_xml(&lease.key),
+                    format_datetime(lease.until)
+                );
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if !self.present.is_empty() {
+            html.push_str("<h2>Present</h2>\n<ul>\n");
+            for member in &self.present {
+                let _ = writeln!(html, "<li>{}</li>", escape_xml(member));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        let sensors = self.sensor_lines();
+        if !sensors.is_empty() {
+            html.push_str("<h2>Sensors</h2>\n<ul>\n");
+            for sensor in sensors {
+                let _ = writeln!(html, "<li>{}</li>", escape_xml(&sensor));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("<h2>History</h2>\n<ul>\n");
+        for change in &self.history {
+            let _ = write!(
+                html,
+                "<li>{} {}",
+                format_datetime(change.timestamp),
+                if change.open { "open" } else { "closed" }
+            );
+            if let Some(person) = &change.trigger_person {
+                let _ = write!(html, " by {}", escape_xml(person));
+            }
+            if let Some(message) = &change.message {
+                let _ = write!(html, ": {}", escape_xml(message));
+            }
+            if !change.published {
+                html.push_str(" (requested)");
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n</body>\n</html>\n");
+        html
+    }
+}
@@ -0,0 +1,221 @@
+use crate::state::SpaceGuard;
+use prometheus::{
+    Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    serde::json::{serde_json, Value},
+    Data, Request, Response,
+};
+use std::time::Instant;
+
+const NAMESPACE: &str = "spaceapi";
+
+/// Admin requests counted per API key
+#[derive(Debug, Clone, Copy)]
+pub enum AdminAction {
+    Open,
+    Close,
+    KeepOpen,
+    Heartbeat,
+    ListLeases,
+    RevokeLease,
+    SetMode,
+    LiftMode,
+    Members,
+    CheckIn,
+    CheckOut,
+    IncomingWebhook,
+}
+
+impl AdminAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AdminAction::Open => "open",
+            AdminAction::Close => "close",
+            AdminAction::KeepOpen => "keep_open",
+            AdminAction::Heartbeat => "heartbeat",
+            AdminAction::ListLeases => "list_leases",
+            AdminAction::RevokeLease => "revoke_lease",
+            AdminAction::SetMode => "set_mode",
+            AdminAction::LiftMode => "lift_mode",
+            AdminAction::Members => "members",
+            AdminAction::CheckIn => "check_in",
+            AdminAction::CheckOut => "check_out",
+            AdminAction::IncomingWebhook => "incoming_webhook",
+        }
+    }
+}
+
+/// Prometheus metrics of the server
+pub struct Metrics {
+    registry: Registry,
+    space_open: IntGauge,
+    keep_open_remaining: Gauge,
+    sensors: GaugeVec,
+    admin_requests: IntCounterVec,
+    auth_failures: IntCounter,
+    http_requests: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, String> {
+        let registry = Registry::new();
+        let opts = |name: &str, help: &str| Opts::new(name, help).namespace(NAMESPACE);
+
+        let space_open = IntGauge::with_opts(opts("space_open", "Whether the space is open"))
+            .map_err(|err| format!("{err}"))?;
+        let keep_open_remaining = Gauge::with_opts(opts(
+            "keep_open_remaining_seconds",
+            "Seconds until the keep open request expires",
+        ))
+        .map_err(|err| format!("{err}"))?;
+        let sensors = GaugeVec::new(
+            opts("sensor_value", "Values of the published sensors"),
+            &["sensor", "name", "location"],
+        )
+        .map_err(|err| format!("{err}"))?;
+        let admin_requests = IntCounterVec::new(
+            opts("admin_requests_total", "Admin requests per API key"),
+            &["action", "key"],
+        )
+        .map_err(|err| format!("{err}"))?;
+        let auth_failures = IntCounter::with_opts(opts(
+            "auth_failures_total",
+            "Requests rejected because of a missing or invalid API key",
+        ))
+        .map_err(|err| format!("{err}"))?;
+        let http_requests = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Latency of HTTP requests")
+                .namespace(NAMESPACE),
+            &["method", "route", "status"],
+        )
+        .map_err(|err| format!("{err}"))?;
+
+        registry
+            .register(Box::new(space_open.clone()))
+            .and_then(|_| registry.register(Box::new(keep_open_remaining.clone())))
+            .and_then(|_| registry.register(Box::new(sensors.clone())))
+            .and_then(|_| registry.register(Box::new(admin_requests.clone())))
+            .and_then(|_| registry.register(Box::new(auth_failures.clone())))
+            .and_then(|_| registry.register(Box::new(http_requests.clone())))
+            .map_err(|err| format!("Can't register metrics: {err}"))?;
+
+        Ok(Metrics {
+            registry,
+            space_open,
+            keep_open_remaining,
+            sensors,
+            admin_requests,
+            auth_failures,
+            http_requests,
+        })
+    }
+
+    pub fn admin_request(&self, action: AdminAction, key: &str) {
+        self.admin_requests
+            .with_label_values(&[action.as_str(), key])
+            .inc();
+    }
+
+    pub fn auth_failure(&self) {
+        self.auth_failures.inc();
+    }
+
+    /// Updates the sensor gauges, every sensor with a numeric `value` is exported
+    fn update_sensors(&self, sensors: Option<&spaceapi_dezentrale::sensors::Sensors>) {
+        self.sensors.reset();
+        let Some(Value::Object(sensors)) = sensors.and_then(|sensors| serde_json::to_value(sensors).ok())
+        else {
+            return;
+        };
+        for (kind, entries) in sensors {
+            let Value::Array(entries) = entries else {
+                continue;
+            };
+            for entry in entries {
+                let label = |name: &str| {
+                    entry
+                        .get(name)
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                let value = entry
+                    .get("value")
+                    .and_then(Value::as_f64)
+                    .or_else(|| entry.get("value").and_then(Value::as_bool).map(f64::from));
+                if let Some(value) = value {
+                    self.sensors
+                        .with_label_values(&[&kind, &label("name"), &label("location")])
+                        .set(value);
+                }
+            }
+        }
+    }
+
+    /// Updates the gauges and encodes all metrics in the text format
+    pub async fn render(
+        &self,
+        space: &SpaceGuard,
+        template: &spaceapi_dezentrale::Status,
+    ) -> Result<String, String> {
+        let state = space.state().await;
+        self.space_open.set(i64::from(state.is_open()));
+        let remaining = state
+            .keep_open_until()
+            .and_then(|until| until.duration_since(space.now()).ok())
+            .unwrap_or_default();
+        self.keep_open_remaining.set(remaining.as_secs_f64());
+        self.update_sensors(state.sensors(template).as_ref());
+        drop(state);
+
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|err| format!("Can't encode metrics: {err}"))?;
+        String::from_utf8(buffer).map_err(|err| format!("Can't encode metrics: {err}"))
+    }
+}
+
+/// Start time of a request, used to measure its latency
+struct RequestStart(Option<Instant>);
+
+/// Fairing to record the latency of all requests
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(start) = request.local_cache(|| RequestStart(None)).0 else {
+            return;
+        };
+        if let Some(metrics) = request.rocket().state::<Metrics>() {
+            // Only use the route pattern as label, to keep the amount of series bounded
+            let route = request
+                .route()
+                .map(|route| route.uri.path().to_string())
+                .unwrap_or_else(|| "unmatched".to_string());
+            metrics
+                .http_requests
+                .with_label_values(&[
+                    request.method().as_str(),
+                    &route,
+                    &response.status().code.to_string(),
+                ])
+                .observe(start.elapsed().as_secs_f64());
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    clock::Clock,
+    config::{NetworkPresenceConfig, NetworkSource},
+    presence::{KnownMember, NetworkDevices},
+    state::SpaceGuard,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// Id and key name of the lease keeping the space open while devices are present
+pub const LEASE: &str = "network";
+
+/// MAC address in lower case separated by colons, `None` if it's invalid
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let octets: Vec<&str> = mac.trim().split([':', '-']).collect();
+    let valid = octets.len() == 6
+        && octets
+            .iter()
+            .all(|octet| octet.len() == 2 && octet.chars().all(|c| c.is_ascii_hexdigit()));
+    valid.then(|| octets.join(":").to_ascii_lowercase())
+}
+
+/// MAC addresses of the active leases of a dnsmasq leases file at `now`
+///
+/// Every line is `<expiry> <mac> <ip> <hostname> <client id>`, an expiry of zero never expires.
+pub fn parse_dnsmasq(content: &str, now: SystemTime) -> Vec<String> {
+    let now = crate::unix_timestamp(now);
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            // The DUID line of IPv6 leases has no numeric expiry
+            let expiry: u64 = fields.next()?.parse().ok()?;
+            let mac = normalize_mac(fields.next()?)?;
+            (expiry == 0 || expiry > now).then_some(mac)
+        })
+        .collect()
+}
+
+/// End of an ISC DHCP lease, e.g. `4 2026/10/15 22:00:00`, in UTC
+fn parse_isc_time(value: &str) -> Option<SystemTime> {
+    let (_, datetime) = value.split_once(' ')?;
+    let time = chrono::NaiveDateTime::parse_from_str(datetime, "%Y/%m/%d %H:%M:%S").ok()?;
+    let timestamp = u64::try_from(time.and_utc().timestamp()).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp))
+}
+
+/// MAC addresses of the active leases of an ISC DHCP leases file at `now`
+///
+/// The file is a journal, so the last block of an address overrides the previous ones.
+pub fn parse_isc_dhcp(content: &str, now: SystemTime) -> Vec<String> {
+    let mut leases: BTreeMap<&str, Option<String>> = BTreeMap::new();
+    let mut current: Option<(&str, Option<String>, bool, bool)> = None;
+    for line in content.lines() {
+        let line = line.trim().trim_end_matches(';');
+        if let Some(address) = line.strip_prefix("lease ") {
+            let address = address.trim_end_matches('{').trim();
+            current = Some((address, None, false, true));
+            continue;
+        }
+        let Some((address, mac, active, alive)) = current.as_mut() else {
+            continue;
+        };
+        if line == "}" {
+            leases.insert(address, mac.take().filter(|_| *active && *alive));
+            current = None;
+        } else if let Some(state) = line.strip_prefix("binding state ") {
+            *active = state == "active";
+        } else if let Some(ends) = line.strip_prefix("ends ") {
+            *alive = ends == "never" || parse_isc_time(ends).is_some_and(|ends| ends > now);
+        } else if let Some(hardware) = line.strip_prefix("hardware ethernet ") {
+            *mac = normalize_mac(hardware);
+        }
+    }
+    leases.into_values().flatten().collect()
+}
+
+/// MAC addresses of the complete entries of an ARP table like `/proc/net/arp`
+pub fn parse_arp(content: &str) -> Vec<String> {
+    const COMPLETE: u32 = 0x2;
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let flags = u32::from_str_radix(fields.get(2)?.trim_start_matches("0x"), 16).ok()?;
+            let mac = normalize_mac(fields.get(3)?)?;
+            (flags & COMPLETE != 0 && mac != "00:00:00:00:00:00").then_some(mac)
+        })
+        .collect()
+}
+
+/// Task scanning the file periodically
+struct Scanner {
+    handle: JoinHandle<()>,
+    shutdown: Arc<Notify>,
+}
+
+/// Presence derived from the devices on the network, see [`NetworkPresenceConfig`]
+#[derive(Clone)]
+pub struct NetworkPresence {
+    config: Arc<NetworkPresenceConfig>,
+    clock: Arc<dyn Clock>,
+    scanner: Arc<Mutex<Option<Scanner>>>,
+}
+
+impl NetworkPresence {
+    pub fn new(config: NetworkPresenceConfig, clock: Arc<dyn Clock>) -> Self {
+        NetworkPresence {
+            config: Arc::new(config),
+            clock,
+            scanner: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Counts the devices of the MAC addresses `macs`, ignoring duplicates
+    pub fn count(&self, macs: &[String]) -> NetworkDevices {
+        let mut devices = NetworkDevices::default();
+        let mut seen: Vec<&str> = vec![];
+        for mac in macs {
+            if seen.contains(&mac.as_str()) {
+                continue;
+            }
+            seen.push(mac);
+            if self
+                .config
+                .ignore
+                .iter()
+                .any(|ignored| normalize_mac(ignored).as_ref() == Some(mac))
+            {
+                continue;
+            }
+            let device = self
+                .config
+                .devices
+                .iter()
+                .find(|device| normalize_mac(&device.mac).as_ref() == Some(mac));
+            match device {
+                Some(device) => match devices.known.iter_mut().find(|known| known.name == device.name) {
+                    Some(known) => known.public |= device.public,
+                    None => devices.known.push(KnownMember {
+                        name: device.name.clone(),
+                        public: device.public,
+                    }),
+                },
+                None if self.config.count_anonymous => devices.anonymous += 1,
+                None => {}
+            }
+        }
+        devices
+    }
+
+    /// Reads the file and counts the devices present now
+    pub async fn scan(&self) -> Result<NetworkDevices, String> {
+        let path = self.config.path();
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|err| format!("Can't read `{}`: {err}", path.display()))?;
+        let now = self.clock.now();
+        let macs = match self.config.source {
+            NetworkSource::Dnsmasq => parse_dnsmasq(&content, now),
+            NetworkSource::IscDhcp => parse_isc_dhcp(&content, now),
+            NetworkSource::Arp => parse_arp(&content),
+        };
+        Ok(self.count(&macs))
+    }
+
+    /// Scans the file and publishes the devices, keeping the space open while any is present
+    ///
+    /// A space opened by a client is left as it is. The lease lasts four scans and is renewed once
+    /// it ends within two, so a single failed scan doesn't close the space.
+    pub async fn update(&self, space: &SpaceGuard) {
+        let devices = match self.scan().await {
+            Ok(devices) => devices,
+            Err(err) => {
+                log::warn!("{err}");
+                return;
+            }
+        };
+        log::trace!("Network devices present: {devices:?}");
+        let present = !devices.is_empty();
+        space.set_network_devices(devices).await;
+        if !self.config.keep_open {
+            return;
+        }
+        if present {
+            let interval = self.config.interval;
+            if let Err(err) = space.renew_lease(LEASE, LEASE, interval * 4, interval * 2).await {
+                log::debug!("Devices present, but the space isn't kept open: {err}");
+            }
+        } else {
+            match space.revoke_lease(LEASE, LEASE).await {
+                Ok(true) => log::info!("No devices present anymore"),
+                Ok(false) => {}
+                Err(err) => log::error!("Can't revoke the lease of the network presence: {err}"),
+            }
+        }
+    }
+
+    /// Starts the task scanning the file of `space` every interval
+    pub fn start_scanner(&self, space: SpaceGuard) {
+        let instance = self.clone();
+        let shutdown = Arc::new(Notify::new());
+        let stop = shutdown.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let next = instance.clock.now() + instance.config.interval;
+                tokio::select! {
+                    _ = instance.clock.sleep_until(next) => instance.update(&space).await,
+                    _ = stop.notified() => break,
+                }
+            }
+            log::debug!("Network presence scanner stopped");
+        });
+        *self.scanner.lock().unwrap() = Some(Scanner { handle, shutdown });
+    }
+
+    /// Stops the scanner and waits till it finished
+    pub async fn stop_scanner(&self) {
+        let scanner = self.scanner.lock().unwrap().take();
+        if let Some(scanner) = scanner {
+            scanner.shutdown.notify_one();
+            if let Err(err) = scanner.handle.await {
+                log::error!("Network presence scanner failed: {err}");
+            }
+        }
+    }
+}
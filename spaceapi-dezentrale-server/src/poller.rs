@@ -0,0 +1,212 @@
+use crate::{
+    clock::Clock,
+    command::Reading,
+    config::PollerConfig,
+    source::{RequestedState, Runner},
+    state::SpaceGuard,
+};
+use rocket::serde::json::Value;
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// Outcome of the previous polls
+struct PollState {
+    /// Time of the last poll which read any value, or of the start before the first one
+    last_success: SystemTime,
+    /// Whether the values were withdrawn because no poll succeeded within `stale_after`
+    stale: bool,
+}
+
+/// Device answering with a JSON document, feeding sensors or the state of the space, see
+/// [`PollerConfig`]
+#[derive(Clone)]
+pub struct Poller {
+    config: Arc<PollerConfig>,
+    client: reqwest::Client,
+    clock: Arc<dyn Clock>,
+    /// Published sensors, to reject values which don't fit before they're published
+    template: Arc<Option<spaceapi_dezentrale::sensors::Sensors>>,
+    state: Arc<Mutex<PollState>>,
+    /// State requested by each value of the previous polls, requests are only sent on changes
+    requested: Arc<Vec<RequestedState>>,
+}
+
+impl Poller {
+    pub fn new(
+        config: PollerConfig,
+        template: &spaceapi_dezentrale::Status,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, String> {
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(format!("{}/{}", crate::SOFTWARE, crate::VERSION))
+            .timeout(config.timeout)
+            .build()
+            .map_err(|err| format!("Can't build client of poller `{}`: {err}", config.name))?;
+        let state = PollState {
+            last_success: clock.now(),
+            stale: false,
+        };
+        let requested = config.values.iter().map(|_| RequestedState::default()).collect();
+        Ok(Poller {
+            config: Arc::new(config),
+            client,
+            clock,
+            template: Arc::new(template.sensors.clone()),
+            state: Arc::new(Mutex::new(state)),
+            requested: Arc::new(requested),
+        })
+    }
+
+    /// Whether no poll succeeded within `stale_after`
+    pub fn is_stale(&self) -> bool {
+        self.state.lock().unwrap().stale
+    }
+
+    /// Fetches the document, fails unless the device answers with a success status and JSON
+    pub async fn fetch(&self) -> Result<Value, String> {
+        let name = &self.config.name;
+        let response = self
+            .client
+            .get(&self.config.url)
+            .send()
+            .await
+            .map_err(|err| format!("Poller `{name}` can't fetch `{}`: {err}", self.config.url))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Poller `{name}`: `{}` answered with {}",
+                self.config.url,
+                response.status()
+            ));
+        }
+        response.json().await.map_err(|err| {
+            format!(
+                "Poller `{name}`: `{}` answered with invalid JSON: {err}",
+                self.config.url
+            )
+        })
+    }
+
+    /// Extracts and maps the values of `document`, `None` for values which are missing, don't
+    /// fit or mean neither open nor closed
+    pub fn read(&self, document: &Value) -> Vec<Option<Reading>> {
+        let name = &self.config.name;
+        self.config
+            .values
+            .iter()
+            .map(|config| {
+                let Some(value) = document.pointer(&config.pointer) else {
+                    log::warn!("Poller `{name}` found no value at `{}`", config.pointer);
+                    return None;
+                };
+                if let Some(state) = &config.state {
+                    let open = state.state(value);
+                    if open.is_none() {
+                        log::trace!("Poller `{name}` value `{value}` means neither open nor closed");
+                    }
+                    return open.map(Reading::State);
+                }
+                let target = config.sensor.clone()?;
+                let mut published = (*self.template).clone();
+                match target.apply(&mut published, value) {
+                    Ok(()) => Some(Reading::Sensor(target, value.clone())),
+                    Err(err) => {
+                        log::warn!("Poller `{name}`: {err}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Polls the device once and publishes its values
+    ///
+    /// A state is only requested when it differs from the previous poll, see [`RequestedState`].
+    /// A failed poll, or one without any value, keeps the previous values till `stale_after`
+    /// passed since the last successful one.
+    pub async fn update(&self, space: &SpaceGuard) {
+        let document = match self.fetch().await {
+            Ok(document) => document,
+            Err(err) => {
+                log::warn!("{err}");
+                self.check_stale(space).await;
+                return;
+            }
+        };
+        let name = &self.config.name;
+        let readings = self.read(&document);
+        if readings.iter().all(Option::is_none) {
+            log::warn!("Poller `{name}` read no values");
+            self.check_stale(space).await;
+            return;
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            state.last_success = self.clock.now();
+            if state.stale {
+                state.stale = false;
+                log::info!("Poller `{name}` recovered");
+            }
+        }
+        let source = format!("Poller `{name}`");
+        for (reading, requested) in readings.into_iter().zip(self.requested.iter()) {
+            match reading {
+                Some(Reading::Sensor(target, value)) => space.set_sensor(target, value).await,
+                Some(Reading::State(open)) => requested.request(space, open, &source).await,
+                None => {}
+            }
+        }
+    }
+
+    /// Withdraws the values if `stale_after` passed since the last successful poll
+    ///
+    /// The sensors publish the values of the template again and, if the poller has a state, the
+    /// stale status is published unless a mode is set.
+    async fn check_stale(&self, space: &SpaceGuard) {
+        let stale_after = self.config.stale_after;
+        {
+            let mut state = self.state.lock().unwrap();
+            if stale_after.is_zero() || state.stale || self.clock.now() < state.last_success + stale_after {
+                return;
+            }
+            state.stale = true;
+        }
+        self.requested.iter().for_each(RequestedState::reset);
+        let name = &self.config.name;
+        log::warn!("Poller `{name}` got no values for {stale_after:?}, withdrawing them");
+        for target in self
+            .config
+            .values
+            .iter()
+            .filter_map(|value| value.sensor.as_ref())
+        {
+            space.clear_sensor(target).await;
+        }
+        let has_state = self.config.values.iter().any(|value| value.state.is_some());
+        let Some(status) = self.config.stale_status.filter(|_| has_state) else {
+            return;
+        };
+        let message = Some(format!("No values from {name}"));
+        match space.stale(status, message).await {
+            Ok(Some(mode)) => log::debug!("Space is set to {}, keeping it", mode.name()),
+            Ok(None) => {}
+            Err(err) => log::error!("Can't publish stale poller `{name}`: {err}"),
+        }
+    }
+
+    /// Starts the task polling the device for `space` right away and every interval
+    pub fn start(&self, space: SpaceGuard) -> Runner {
+        let instance = self.clone();
+        Runner::start(
+            format!("Poller `{}`", self.config.name),
+            self.clock.clone(),
+            self.config.interval,
+            move || {
+                let instance = instance.clone();
+                let space = space.clone();
+                async move { instance.update(&space).await }
+            },
+        )
+    }
+}
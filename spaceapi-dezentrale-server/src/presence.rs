@@ -0,0 +1,112 @@
+use spaceapi_dezentrale::sensors::{PeopleNowPresentSensor, SensorMetadata, Sensors};
+use std::time::SystemTime;
+
+/// Member who checked in, present till checking out or till the check-in times out
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CheckIn {
+    pub member: String,
+    /// Whether the member agreed to publish the name
+    pub public: bool,
+    pub since: SystemTime,
+    pub until: SystemTime,
+}
+
+/// Member whose registered device is on the network
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KnownMember {
+    pub name: String,
+    /// Whether the member agreed to publish the name
+    pub public: bool,
+}
+
+/// Devices found on the network by the latest scan
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct NetworkDevices {
+    /// Members with a registered device, every member once
+    pub known: Vec<KnownMember>,
+    /// Amount of counted devices which aren't registered
+    pub anonymous: u64,
+}
+
+impl NetworkDevices {
+    /// Whether any counted device is present
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty() && self.anonymous == 0
+    }
+}
+
+/// People present in the space, published as `people_now_present` sensor
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Presence {
+    pub check_ins: Vec<CheckIn>,
+    pub network: NetworkDevices,
+    /// Whether the first check-in opened the space and nobody changed it since, only then the
+    /// last check-out closes it
+    pub opened: bool,
+}
+
+impl Presence {
+    /// Members present, the checked in ones and those with a device on the network
+    fn members(&self) -> Vec<(&str, bool)> {
+        let mut members: Vec<(&str, bool)> = self
+            .check_ins
+            .iter()
+            .map(|check_in| (check_in.member.as_str(), check_in.public))
+            .collect();
+        for known in &self.network.known {
+            match members.iter_mut().find(|(name, _)| *name == known.name) {
+                Some((_, public)) => *public |= known.public,
+                None => members.push((&known.name, known.public)),
+            }
+        }
+        members
+    }
+
+    /// Amount of people present, a member with a device on the network counts once
+    pub fn count(&self) -> u64 {
+        self.members().len() as u64 + self.network.anonymous
+    }
+
+    /// Names of all present members
+    pub fn names(&self) -> Vec<String> {
+        self.members()
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Names of the present members who agreed to publish them
+    pub fn public_names(&self) -> Vec<String> {
+        self.members()
+            .into_iter()
+            .filter(|(_, public)| *public)
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// End of the check-in which times out first
+    pub fn next_timeout(&self) -> Option<SystemTime> {
+        self.check_ins.iter().map(|check_in| check_in.until).min()
+    }
+
+    /// Sensor with the amount of people and the public names, described by `metadata`
+    pub fn sensor(&self, metadata: SensorMetadata) -> PeopleNowPresentSensor {
+        let names = self.public_names();
+        PeopleNowPresentSensor {
+            metadata,
+            names: (!names.is_empty()).then_some(names),
+            value: self.count(),
+        }
+    }
+
+    /// Replaces the `people_now_present` sensors, keeping the metadata of the first one
+    pub fn publish(&self, sensors: &mut Option<Sensors>) {
+        let sensors = sensors.get_or_insert_with(Sensors::default);
+        let metadata = sensors
+            .people_now_present
+            .first()
+            .map(|sensor| sensor.metadata.clone())
+            .unwrap_or_default();
+        sensors.people_now_present = vec![self.sensor(metadata)];
+    }
+}
@@ -0,0 +1,134 @@
+use crate::config::RateLimitConfig;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Amount of tracked clients after which expired entries are removed
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Counter of a fixed time window
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Window { start: now, count: 0 }
+    }
+
+    /// Counts a hit, returns the time till the window ends if the limit is exceeded
+    fn hit(&mut self, now: Instant, length: Duration, limit: u32) -> Result<(), Duration> {
+        if now.duration_since(self.start) >= length {
+            *self = Window::new(now);
+        }
+        if self.count >= limit {
+            return Err(length.saturating_sub(now.duration_since(self.start)));
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn is_expired(&self, now: Instant, length: Duration) -> bool {
+        now.duration_since(self.start) >= length
+    }
+}
+
+#[derive(Debug)]
+struct Client {
+    requests: Window,
+    failures: Window,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    global: Window,
+    clients: HashMap<Option<IpAddr>, Client>,
+}
+
+/// Rate limiting and lockout after failed API key attempts for the admin routes
+///
+/// Requests without a known remote address share one entry.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let now = Instant::now();
+        RateLimiter {
+            config,
+            state: Mutex::new(LimiterState {
+                global: Window::new(now),
+                clients: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Counts a request, returns the time to wait if the client is limited or locked out
+    pub fn check(&self, ip: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let window = self.config.window;
+        let mut state = self.state.lock().unwrap();
+        if state.clients.len() > PRUNE_THRESHOLD {
+            state.clients.retain(|_, client| {
+                !client.requests.is_expired(now, window)
+                    || !client.failures.is_expired(now, window)
+                    || client.locked_until.is_some_and(|until| until > now)
+            });
+        }
+
+        let client = state.clients.entry(ip).or_insert_with(|| Client {
+            requests: Window::new(now),
+            failures: Window::new(now),
+            locked_until: None,
+        });
+        if let Some(until) = client.locked_until {
+            if until > now {
+                return Err(until - now);
+            }
+            client.locked_until = None;
+        }
+        client.requests.hit(now, window, self.config.per_ip)?;
+        state.global.hit(now, window, self.config.global)
+    }
+
+    /// Records a failed API key attempt and locks the client out if there are too many
+    pub fn record_failure(&self, ip: Option<IpAddr>, now: Instant) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let Some(client) = state.clients.get_mut(&ip) else {
+            return;
+        };
+        if client
+            .failures
+            .hit(
+                now,
+                self.config.window,
+                self.config.max_failures.saturating_sub(1),
+            )
+            .is_err()
+        {
+            client.locked_until = Some(now + self.config.lockout);
+            client.failures = Window::new(now);
+            log::warn!(
+                "Locked out {} for {:?} after {} failed API key attempts",
+                ip.map(|ip| ip.to_string())
+                    .unwrap_or("unknown client".to_string()),
+                self.config.lockout,
+                self.config.max_failures
+            );
+        }
+    }
+}
@@ -1,102 +1,610 @@
 use crate::{
-    config::{ApiKey, StatusDisplayTypes},
-    state::SpaceGuard,
+    cache::{Cached, Conditional},
+    calendar::Calendar,
+    config::{
+        ApiKeys, CacheControlConfig, KeepOpenLimits, KeyScope, MembersConfig, NamedApiKey, WebhookAction,
+    },
+    display::StatusDisplays,
+    feed::Feed,
+    heartbeat::Heartbeat,
+    incoming::{IncomingWebhooks, WebhookMatch},
+    members::MemberStatus,
+    metrics::{AdminAction, Metrics},
+    ratelimit::RateLimiter,
+    snapshot::{Rendered, StatusReceiver, StatusSnapshot},
+    state::{Mode, SpaceGuard, MAX_DURATION},
 };
+use base64::Engine;
 use rocket::{
-    fairing::{Fairing, Info, Kind},
-    http::{
-        hyper::header::{
-            ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
-        },
-        ContentType, Header, Status,
-    },
-    outcome::Outcome,
+    data::{ByteUnit, Data},
+    http::{ContentType, Header, HeaderMap, Method, Status},
+    outcome::{try_outcome, Outcome},
     request::{self, FromRequest, Request},
-    response::Response,
-    serde::json::Json,
+    serde::json::{Json, Value},
     State,
 };
-use std::time::SystemTime;
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// API key of the request, from the `X-API-Key` header or, if `basic` is set, the password of a
+/// basic authentication
+fn request_api_key(req: &Request<'_>, basic: bool) -> Option<String> {
+    if let Some(api_key) = req.headers().get_one("X-API-Key") {
+        return Some(api_key.to_string());
+    }
+    if !basic {
+        return None;
+    }
+    // Browsers only send a key as basic authentication, the user name is ignored
+    let credentials = req.headers().get_one("Authorization")?.strip_prefix("Basic ")?;
+    let credentials = base64::engine::general_purpose::STANDARD
+        .decode(credentials.trim())
+        .ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (_, password) = credentials.split_once(':')?;
+    Some(password.to_string())
+}
+
+/// Rejects clients which failed to authenticate too often, see [`RateLimiter`]
+fn check_rate_limit(req: &Request<'_>) -> Result<(), Status> {
+    if let Some(limiter) = req.rocket().state::<RateLimiter>() {
+        if let Err(retry_after) = limiter.check(req.client_ip(), Instant::now()) {
+            req.local_cache(|| RetryAfter(Some(retry_after)));
+            return Err(Status::TooManyRequests);
+        }
+    }
+    Ok(())
+}
+
+/// Logs and records a failed attempt of the client `ip` to authenticate on `uri`
+fn failed_attempt(
+    ip: Option<IpAddr>,
+    uri: &dyn std::fmt::Display,
+    limiter: Option<&RateLimiter>,
+    metrics: Option<&Metrics>,
+) {
+    log::warn!(
+        "Failed API key attempt from {} on `{uri}`",
+        ip.map(|ip| ip.to_string())
+            .unwrap_or("unknown client".to_string()),
+    );
+    if let Some(limiter) = limiter {
+        limiter.record_failure(ip, Instant::now());
+    }
+    if let Some(metrics) = metrics {
+        metrics.auth_failure();
+    }
+}
+
+/// Finds the configured API key of the request, checks the rate limits and records failures
+///
+/// Basic authentication is only accepted with `basic`, as browsers send cached credentials along
+/// with requests of other sites.
+fn authenticate(req: &Request<'_>, basic: bool) -> request::Outcome<NamedApiKey, &'static str> {
+    if let Err(status) = check_rate_limit(req) {
+        return Outcome::Error((status, "Too many requests"));
+    }
+
+    if let Some(api_key) = request_api_key(req, basic) {
+        if let Some(api_keys) = req.rocket().state::<ApiKeys>() {
+            if let Some(key) = api_keys.find(&api_key) {
+                if let Some(heartbeat) = req.rocket().state::<Heartbeat>() {
+                    heartbeat.beat(&key.name);
+                }
+                return Outcome::Success(key.clone());
+            }
+        }
+    }
+
+    failed_attempt(
+        req.client_ip(),
+        req.uri(),
+        req.rocket().state::<RateLimiter>(),
+        req.rocket().state::<Metrics>(),
+    );
+    Outcome::Error((Status::Unauthorized, "Api key missing"))
+}
+
+/// Guard for admin routes, which contains the name of the used API key
+///
+/// Keys with the [`KeyScope::Read`] scope are rejected with `403 Forbidden`.
+pub struct AuthorizedKey {
+    pub name: String,
+    /// Limits of the keep open durations requested with the key
+    pub keep_open: KeepOpenLimits,
+}
 
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for ApiKey {
+impl<'r> FromRequest<'r> for AuthorizedKey {
     type Error = &'static str;
 
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        if let Some(api_key) = req.headers().get_one("X-API-Key") {
-            if let Some(api_key_config) = req.rocket().state::<ApiKey>() {
-                if api_key == api_key_config.0 {
-                    return Outcome::Success(ApiKey(api_key.to_string()));
-                }
-            }
+        let key = try_outcome!(authenticate(req, false));
+        if key.scope != KeyScope::Admin {
+            log::warn!("API key `{}` can't access `{}`", key.name, req.uri());
+            return Outcome::Error((Status::Forbidden, "API key is read only"));
         }
+        Outcome::Success(AuthorizedKey {
+            name: key.name,
+            keep_open: key.keep_open,
+        })
+    }
+}
 
-        Outcome::Error((Status::Unauthorized, "Api key missing"))
+/// Guard for the members view and the check-in, which accepts API keys of any scope
+///
+/// Only `GET` requests, which don't change anything, accept the key as basic authentication.
+pub struct MemberKey {
+    pub name: String,
+    pub scope: KeyScope,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MemberKey {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let key = try_outcome!(authenticate(req, req.method() == Method::Get));
+        Outcome::Success(MemberKey {
+            name: key.name,
+            scope: key.scope,
+        })
     }
 }
 
-pub struct Cors;
+/// Guard for incoming webhooks, which authenticate with the body, so only the rate limits are
+/// checked before the route
+pub struct WebhookClient<'r> {
+    pub ip: Option<IpAddr>,
+    pub headers: &'r HeaderMap<'r>,
+}
 
-/// Implementation for CORS
-///
-/// Inspired by
-/// [Stackoverflow](https://stackoverflow.com/questions/62412361/how-to-set-up-cors-or-options-for-rocket-rs)
 #[rocket::async_trait]
-impl Fairing for Cors {
-    fn info(&self) -> Info {
-        Info {
-            name: "CORS Settings",
-            kind: Kind::Response,
+impl<'r> FromRequest<'r> for WebhookClient<'r> {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        if let Err(status) = check_rate_limit(req) {
+            return Outcome::Error((status, "Too many requests"));
         }
+        Outcome::Success(WebhookClient {
+            ip: req.client_ip(),
+            headers: req.headers(),
+        })
     }
+}
+
+/// Time a rate limited client has to wait, cached for the catcher
+struct RetryAfter(Option<Duration>);
+
+#[derive(Responder)]
+#[response(status = 429)]
+pub struct TooManyRequests {
+    inner: &'static str,
+    retry_after: Header<'static>,
+}
+
+/// Answers rate limited requests with the time to wait
+#[catch(429)]
+pub fn too_many_requests(req: &Request<'_>) -> TooManyRequests {
+    let retry_after = req.local_cache(|| RetryAfter(None)).0.unwrap_or_default();
+    // Round up to full seconds, so the client doesn't retry too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    TooManyRequests {
+        inner: "Too many requests",
+        retry_after: Header::new("Retry-After", seconds.to_string()),
+    }
+}
+
+#[derive(Responder)]
+#[response(status = 401)]
+pub struct MembersUnauthorized {
+    inner: &'static str,
+    authenticate: Header<'static>,
+}
 
-    async fn on_response<'a>(&self, _request: &'a Request<'_>, response: &mut Response<'a>) {
-        // Allow GET from all locations
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_HEADERS.as_str(), "X-API-Key"));
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_METHODS.as_str(), "POST,GET"));
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), "*"));
+/// Asks browsers for the API key of the members view, the user name is ignored
+#[catch(401)]
+pub fn members_unauthorized() -> MembersUnauthorized {
+    MembersUnauthorized {
+        inner: "Api key missing",
+        authenticate: Header::new("WWW-Authenticate", r#"Basic realm="members", charset="UTF-8""#),
     }
 }
 
-#[post("/admin/publish/space-open")]
-pub async fn open_space(_api_key: ApiKey, space: &State<SpaceGuard>) {
-    space.open().await;
+/// Body of a change the current status doesn't allow
+#[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct ConflictResponse {
+    pub error: String,
+    /// Name of the current status
+    pub state: String,
+    /// Message attached to the current status
+    pub message: Option<String>,
+    /// Timestamp (UTC) till the current status lasts, if known
+    pub until: Option<u64>,
+}
+
+/// Error response of the admin routes
+#[derive(Debug, Responder)]
+pub enum Rejection {
+    /// The current status doesn't allow the change, e.g. during maintenance
+    #[response(status = 409)]
+    Conflict(Json<ConflictResponse>),
+    Other(Status),
+}
+
+impl From<Status> for Rejection {
+    fn from(status: Status) -> Self {
+        Rejection::Other(status)
+    }
+}
+
+/// Rejects a change the current status doesn't allow, with the reason for the client
+async fn conflict(space: &SpaceGuard, err: String) -> Rejection {
+    log::warn!("{err}");
+    let space = space.state().await;
+    Rejection::Conflict(Json(ConflictResponse {
+        error: err,
+        state: space.status.name().to_string(),
+        message: space.message.clone(),
+        until: space.status.until().map(crate::unix_timestamp),
+    }))
+}
+
+/// Only signals that the client is alive, see [`HeartbeatConfig`](crate::config::HeartbeatConfig)
+#[post("/admin/heartbeat")]
+pub fn send_heartbeat(api_key: AuthorizedKey, metrics: &State<Metrics>) {
+    metrics.admin_request(AdminAction::Heartbeat, &api_key.name);
+}
+
+#[post("/admin/publish/space-open?<trigger_person>&<message>")]
+pub async fn open_space(
+    api_key: AuthorizedKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    trigger_person: Option<String>,
+    message: Option<String>,
+) -> Result<(), Rejection> {
+    metrics.admin_request(AdminAction::Open, &api_key.name);
+    match space.request_open(trigger_person, message).await {
+        Ok(()) => Ok(()),
+        Err(err) => Err(conflict(space, err).await),
+    }
 }
 
-#[post("/admin/publish/space-close")]
-pub async fn close_space(_api_key: ApiKey, space: &State<SpaceGuard>) {
-    space.close().await;
+#[post("/admin/publish/space-close?<trigger_person>&<message>")]
+pub async fn close_space(
+    api_key: AuthorizedKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    trigger_person: Option<String>,
+    message: Option<String>,
+) -> Result<(), Rejection> {
+    metrics.admin_request(AdminAction::Close, &api_key.name);
+    match space.request_close(trigger_person, message).await {
+        Ok(()) => Ok(()),
+        Err(err) => Err(conflict(space, err).await),
+    }
 }
 
 #[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
 pub struct KeepOpenResponse {
-    /// Timestamp (UTC) till the space stays open
+    /// Timestamp (UTC) till the lease keeps the space open
     pub open_till: u64,
+    /// Id of the lease
+    pub lease: String,
+    /// Seconds the lease lasts, the requested duration bounded by the limits of the API key
+    pub duration: u64,
 }
 
-#[post("/admin/publish/space-keep-open")]
-pub async fn keep_open(_api_key: ApiKey, space: &State<SpaceGuard>) -> Json<KeepOpenResponse> {
-    let till = space.keep_open().await;
+/// Keeps the space open with the lease `lease`, the name of the API key by default
+///
+/// The lease lasts for `duration` seconds, the keep open interval by default, bounded by the keep
+/// open limits of the API key.
+#[post("/admin/publish/space-keep-open?<lease>&<duration>")]
+pub async fn keep_open(
+    api_key: AuthorizedKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    lease: Option<String>,
+    duration: Option<u64>,
+) -> Result<Json<KeepOpenResponse>, Rejection> {
+    metrics.admin_request(AdminAction::KeepOpen, &api_key.name);
+    let lease = lease.unwrap_or_else(|| api_key.name.clone());
+    let requested = match duration {
+        Some(0) => return Err(Status::BadRequest.into()),
+        Some(duration) => Duration::from_secs(duration),
+        None => space.keep_open_interval().await,
+    };
+    let duration = api_key.keep_open.bound(requested).min(MAX_DURATION);
+    if duration != requested {
+        log::debug!(
+            "Keep open duration {requested:?} of `{}` bounded to {duration:?}",
+            api_key.name
+        );
+    }
+    let till = match space.keep_open(&lease, &api_key.name, Some(duration)).await {
+        Ok(till) => till,
+        Err(err) => return Err(conflict(space, err).await),
+    };
     log::debug!("Space will be opened till {till:?}");
-    Json(KeepOpenResponse {
+    Ok(Json(KeepOpenResponse {
         open_till: till.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(),
-    })
+        lease,
+        duration: duration.as_secs(),
+    }))
 }
 
-/// Minimalistic implementation of the index page
-#[get("/")]
-pub async fn index(
+/// Lease keeping the space open
+#[derive(Debug, PartialEq, Eq, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct LeaseResponse {
+    pub id: String,
+    /// Name of the API key which requested the lease
+    pub key: String,
+    /// Timestamp (UTC) till the lease keeps the space open
+    pub until: u64,
+}
+
+#[get("/admin/leases")]
+pub async fn list_leases(
+    api_key: AuthorizedKey,
     space: &State<SpaceGuard>,
-    displays: &State<StatusDisplayTypes>,
+    metrics: &State<Metrics>,
+) -> Json<Vec<LeaseResponse>> {
+    metrics.admin_request(AdminAction::ListLeases, &api_key.name);
+    let leases = space
+        .leases()
+        .await
+        .into_iter()
+        .map(|lease| LeaseResponse {
+            id: lease.id,
+            key: lease.key,
+            until: crate::unix_timestamp(lease.until),
+        })
+        .collect();
+    Json(leases)
+}
+
+/// Revokes a lease of the API key, the space closes if no other lease keeps it open
+#[delete("/admin/leases/<id>")]
+pub async fn revoke_lease(
+    api_key: AuthorizedKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    id: &str,
+) -> Result<(), Rejection> {
+    metrics.admin_request(AdminAction::RevokeLease, &api_key.name);
+    match space.revoke_lease(id, &api_key.name).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Status::NotFound.into()),
+        Err(err) => Err(conflict(space, err).await),
+    }
+}
+
+/// Sets the mode `mode` (`maintenance` or `private_event`) till the timestamp (UTC) `until`
+///
+/// The space is published as closed with the message, open and keep open requests are rejected
+/// till the mode ends or is lifted.
+#[post("/admin/mode/<mode>?<message>&<until>")]
+pub async fn set_mode(
+    api_key: AuthorizedKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    mode: &str,
+    message: Option<String>,
+    until: Option<u64>,
+) -> Result<(), Rejection> {
+    metrics.admin_request(AdminAction::SetMode, &api_key.name);
+    let mode: Mode = mode.parse().map_err(|err| {
+        log::debug!("{err}");
+        Status::NotFound
+    })?;
+    let until = until
+        .map(|until| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_secs(until))
+                .ok_or(Status::BadRequest)
+        })
+        .transpose()?;
+    if until.is_some_and(|until| until <= space.now()) {
+        return Err(Status::BadRequest.into());
+    }
+    match space.set_mode(mode, message, until).await {
+        Ok(()) => {
+            log::info!("Mode {} set by `{}`", mode.name(), api_key.name);
+            Ok(())
+        }
+        Err(err) => Err(conflict(space, err).await),
+    }
+}
+
+/// Lifts the current mode, the space stays closed
+#[delete("/admin/mode")]
+pub async fn lift_mode(
+    api_key: AuthorizedKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+) -> Result<(), Rejection> {
+    metrics.admin_request(AdminAction::LiftMode, &api_key.name);
+    match space.lift_mode().await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Status::NotFound.into()),
+        Err(err) => Err(conflict(space, err).await),
+    }
+}
+
+/// Name of the member checking in or out, only admin keys may name another member than themselves
+fn checked_member(api_key: &MemberKey, member: Option<String>) -> Result<String, Status> {
+    match member {
+        Some(member) if member != api_key.name && api_key.scope != KeyScope::Admin => {
+            log::warn!("API key `{}` can't check in `{member}`", api_key.name);
+            Err(Status::Forbidden)
+        }
+        Some(member) => Ok(member),
+        None => Ok(api_key.name.clone()),
+    }
+}
+
+#[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct CheckInResponse {
+    pub member: String,
+    /// Whether the name is published
+    pub public: bool,
+    /// Timestamp (UTC) of the first check-in
+    pub since: u64,
+    /// Timestamp (UTC) till the member is checked out automatically
+    pub until: u64,
+}
+
+/// Checks in `member`, the name of the API key by default, a repeated check-in renews it
+///
+/// The name is only published in the `people_now_present` sensor if `public` is set.
+#[post("/admin/check-in?<member>&<public>")]
+pub async fn check_in(
+    api_key: MemberKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    member: Option<String>,
+    public: Option<bool>,
+) -> Result<Json<CheckInResponse>, Rejection> {
+    metrics.admin_request(AdminAction::CheckIn, &api_key.name);
+    let member = checked_member(&api_key, member)?;
+    let public = public.unwrap_or(false);
+    let check_in = match space.check_in(&member, public).await {
+        Ok(check_in) => check_in,
+        Err(err) => return Err(conflict(space, err).await),
+    };
+    Ok(Json(CheckInResponse {
+        member,
+        public,
+        since: crate::unix_timestamp(check_in.since),
+        until: crate::unix_timestamp(check_in.until),
+    }))
+}
+
+/// Checks out `member`, the name of the API key by default
+#[post("/admin/check-out?<member>")]
+pub async fn check_out(
+    api_key: MemberKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    member: Option<String>,
+) -> Result<(), Rejection> {
+    metrics.admin_request(AdminAction::CheckOut, &api_key.name);
+    let member = checked_member(&api_key, member)?;
+    match space.check_out(&member).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Status::NotFound.into()),
+        Err(err) => Err(conflict(space, err).await),
+    }
+}
+
+/// Maximum size of the payload of an incoming webhook
+const WEBHOOK_LIMIT: ByteUnit = ByteUnit::Kibibyte(64);
+
+#[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct IncomingWebhookResponse {
+    /// Actions of the matching rules, in the order they were applied
+    pub actions: Vec<WebhookAction>,
+}
+
+/// Applies the rules of the incoming webhook `name` to the JSON payload, see
+/// [`IncomingWebhookConfig`](crate::config::IncomingWebhookConfig)
+///
+/// Payloads without a matching rule are accepted without a change. Sensor values which don't fit
+/// are rejected with `422 Unprocessable Entity` and changes the current status doesn't allow with
+/// `409 Conflict`, both before any change is applied.
+#[post("/admin/webhooks/<name>", data = "<body>")]
+pub async fn incoming_webhook(
+    name: &str,
+    client: WebhookClient<'_>,
+    webhooks: &State<IncomingWebhooks>,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    limiter: &State<RateLimiter>,
+    body: Data<'_>,
+) -> Result<Json<IncomingWebhookResponse>, Rejection> {
+    let webhook = webhooks.find(name).ok_or(Status::NotFound)?;
+    let body = body.open(WEBHOOK_LIMIT).into_bytes().await.map_err(|err| {
+        log::warn!("Can't read payload of incoming webhook `{name}`: {err}");
+        Status::BadRequest
+    })?;
+    if !body.is_complete() {
+        return Err(Status::PayloadTooLarge.into());
+    }
+    if !webhook.authenticate(client.headers, &body) {
+        let uri = format!("/admin/webhooks/{name}");
+        failed_attempt(client.ip, &uri, Some(limiter), Some(metrics));
+        return Err(Status::Unauthorized.into());
+    }
+    metrics.admin_request(AdminAction::IncomingWebhook, name);
+    let payload: Value = rocket::serde::json::serde_json::from_slice(&body).map_err(|err| {
+        log::warn!("Invalid payload of incoming webhook `{name}`: {err}");
+        Status::BadRequest
+    })?;
+    let matches = webhook.evaluate(&payload).map_err(|err| {
+        log::warn!("{err}");
+        Status::UnprocessableEntity
+    })?;
+    let actions = matches.iter().map(WebhookMatch::action).collect();
+    if let Err(err) = space.apply_webhook(name, matches).await {
+        return Err(conflict(space, err).await);
+    }
+    Ok(Json(IncomingWebhookResponse { actions }))
+}
+
+async fn member_status(
+    space: &SpaceGuard,
+    template: &spaceapi_dezentrale::Status,
+    config: &MembersConfig,
+) -> MemberStatus {
+    let now = space.now();
+    let state = space.state().await;
+    MemberStatus::new(&state, template, now, config.history)
+}
+
+/// Detailed status for members, with the fields hidden from the public status routes
+#[get("/admin/members/status")]
+pub async fn get_member_status(
+    api_key: MemberKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    template: &State<spaceapi_dezentrale::Status>,
+    config: &State<MembersConfig>,
+) -> Json<MemberStatus> {
+    metrics.admin_request(AdminAction::Members, &api_key.name);
+    Json(member_status(space, template, config).await)
+}
+
+/// Detailed status for members as HTML page, browsers ask for the API key as password
+#[get("/admin/members")]
+pub async fn get_members_page(
+    api_key: MemberKey,
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
     template: &State<spaceapi_dezentrale::Status>,
+    config: &State<MembersConfig>,
 ) -> (ContentType, String) {
+    metrics.admin_request(AdminAction::Members, &api_key.name);
+    let status = member_status(space, template, config).await;
+    (ContentType::HTML, status.render_html(&template.space))
+}
+
+/// Minimalistic implementation of the index page
+#[get("/")]
+pub fn index(
+    status: &State<StatusReceiver>,
+    template: &State<spaceapi_dezentrale::Status>,
+) -> Result<(ContentType, String), Status> {
     let name = &template.space;
     let logo = &template.logo;
-    let status = if space.is_open().await {
-        &displays.text.open
-    } else {
-        &displays.text.closed
-    };
+    let snapshot = status.borrow().clone();
+    let Rendered(_, text) = snapshot.text.as_ref().ok_or(Status::InternalServerError)?;
+    // The text isn't escaped, it contains the message and trigger person of clients
+    let status = crate::escape_xml(&String::from_utf8_lossy(text));
+    let (name, logo) = (crate::escape_xml(name), crate::escape_xml(logo));
 
     let html = format!(
         r#"<html>
@@ -112,50 +620,204 @@ pub async fn index(
         crate::SOFTWARE,
         crate::VERSION,
     );
-    (ContentType::HTML, html)
+    Ok((ContentType::HTML, html))
+}
+
+/// Responds with a pre-rendered body of the latest snapshot
+fn respond_cached(
+    status: &StatusReceiver,
+    conditional: &Conditional<'_>,
+    max_age: Duration,
+    body: impl FnOnce(&StatusSnapshot) -> Option<Rendered>,
+) -> Result<Cached<Rendered>, Status> {
+    let snapshot = status.borrow().clone();
+    let validators = snapshot.validators.clone();
+    if conditional.is_not_modified(&validators) {
+        return Ok(Cached::not_modified(validators, max_age));
+    }
+    let body = body(&snapshot).ok_or(Status::InternalServerError)?;
+    Ok(Cached::new(body, validators, max_age))
 }
 
 #[get("/spaceapi/v14")]
-pub async fn get_status_v14<'a>(
-    space: &State<SpaceGuard>,
-    template: &State<spaceapi_dezentrale::Status>,
-) -> Json<spaceapi_dezentrale::Status> {
-    let mut status = template.inner().clone();
-    status.api_compatibility = Some(vec![spaceapi_dezentrale::ApiVersion::V14]);
-    status.state = Some(spaceapi_dezentrale::State {
-        open: Some(space.is_open().await),
-        lastchange: Some(crate::unix_timestamp()),
-        ..spaceapi_dezentrale::State::default()
-    });
-    Json(status)
+pub fn get_status_v14(
+    status: &State<StatusReceiver>,
+    cache_control: &State<CacheControlConfig>,
+    conditional: Conditional<'_>,
+) -> Result<Cached<Rendered>, Status> {
+    respond_cached(status, &conditional, cache_control.spaceapi, |snapshot| {
+        Some(snapshot.spaceapi.clone())
+    })
 }
 
 #[get("/status/text")]
-pub async fn get_status_text<'a>(
-    space: &State<SpaceGuard>,
-    displays: &State<StatusDisplayTypes>,
-) -> (ContentType, String) {
-    let status = if space.is_open().await {
-        displays.text.open.clone()
-    } else {
-        displays.text.closed.clone()
-    };
-    (ContentType::Text, status)
+pub fn get_status_text(
+    status: &State<StatusReceiver>,
+    cache_control: &State<CacheControlConfig>,
+    conditional: Conditional<'_>,
+) -> Result<Cached<Rendered>, Status> {
+    respond_cached(status, &conditional, cache_control.text, |snapshot| {
+        snapshot.text.clone()
+    })
 }
 
 #[get("/status/html")]
-pub async fn get_status_html<'a>(
-    space: &State<SpaceGuard>,
-    displays: &State<StatusDisplayTypes>,
+pub fn get_status_html(
+    status: &State<StatusReceiver>,
+    cache_control: &State<CacheControlConfig>,
+    conditional: Conditional<'_>,
+) -> Result<Cached<Rendered>, Status> {
+    respond_cached(status, &conditional, cache_control.html, |snapshot| {
+        snapshot.html.clone()
+    })
+}
+
+#[get("/status/badge.svg")]
+pub fn get_status_badge_svg(
+    status: &State<StatusReceiver>,
+    displays: &State<StatusDisplays>,
 ) -> (ContentType, String) {
-    let status = if space.is_open().await {
-        displays.html.open.clone()
+    let status = status.borrow().status;
+    (
+        ContentType::SVG,
+        crate::badge::render_svg(displays.badge(), status),
+    )
+}
+
+#[get("/status/badge.png")]
+pub fn get_status_badge_png(
+    status: &State<StatusReceiver>,
+    displays: &State<StatusDisplays>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let status = status.borrow().status;
+    let image = crate::badge::render_png(displays.badge(), status).map_err(|err| {
+        log::error!("{err}");
+        Status::InternalServerError
+    })?;
+    Ok((ContentType::PNG, image))
+}
+
+/// Minimal status for devices with few resources
+#[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct StatusLite {
+    /// Whether the space is open, `None` if unknown
+    pub open: Option<bool>,
+    /// Name of the status: `open`, `keep_open`, `closed`, `unknown`, `maintenance` or
+    /// `private_event`
+    pub state: String,
+    /// Timestamp (UTC) of the last change between open and closed
+    pub since: u64,
+    /// Timestamp (UTC) till the space stays open, if kept open, or till the mode lasts, if set
+    pub until: Option<u64>,
+}
+
+#[get("/status/json")]
+pub fn get_status_json(
+    status: &State<StatusReceiver>,
+    cache_control: &State<CacheControlConfig>,
+    conditional: Conditional<'_>,
+) -> Result<Cached<Rendered>, Status> {
+    respond_cached(status, &conditional, cache_control.json, |snapshot| {
+        Some(snapshot.json.clone())
+    })
+}
+
+#[get("/calendar.ics")]
+pub async fn get_calendar(space: &State<SpaceGuard>, calendar: &State<Calendar>) -> (ContentType, String) {
+    let now = space.now();
+    let space = space.state().await;
+    (ContentType::Calendar, calendar.render(&space.history, now))
+}
+
+#[get("/feed.xml")]
+pub async fn get_feed(space: &State<SpaceGuard>, feed: &State<Feed>) -> (ContentType, String) {
+    let space = space.state().await;
+    (
+        ContentType::new("application", "atom+xml"),
+        feed.render(&space.history),
+    )
+}
+
+#[get("/metrics")]
+pub async fn get_metrics(
+    space: &State<SpaceGuard>,
+    metrics: &State<Metrics>,
+    template: &State<spaceapi_dezentrale::Status>,
+) -> Result<(ContentType, String), Status> {
+    let metrics = metrics.render(space, template).await.map_err(|err| {
+        log::error!("{err}");
+        Status::InternalServerError
+    })?;
+    Ok((
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        metrics,
+    ))
+}
+
+/// Liveness probe, answers as long as the server handles requests
+#[get("/healthz")]
+pub fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Result of a single readiness check
+#[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, rocket::serde::Deserialize, rocket::serde::Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+/// Readiness probe, checks the status displays, the scheduler and the persistence
+#[get("/readyz")]
+pub async fn readyz(space: &State<SpaceGuard>, status: &State<StatusReceiver>) -> (Status, Json<Readiness>) {
+    let persistence = space.state().await.history.check_writable();
+    let snapshot = status.borrow().clone();
+    let failed_displays = [
+        ("text", snapshot.text.is_none()),
+        ("html", snapshot.html.is_none()),
+    ]
+    .into_iter()
+    .filter_map(|(name, failed)| failed.then_some(name))
+    .collect::<Vec<_>>();
+    let checks = vec![
+        // The templates of the displays are only rendered with the state
+        ReadinessCheck {
+            name: "config".to_string(),
+            ok: failed_displays.is_empty(),
+            message: (!failed_displays.is_empty())
+                .then(|| format!("Can't render the {} status", failed_displays.join(" and "))),
+        },
+        ReadinessCheck {
+            name: "scheduler".to_string(),
+            ok: space.is_scheduler_running(),
+            message: None,
+        },
+        ReadinessCheck {
+            name: "persistence".to_string(),
+            ok: persistence.is_ok(),
+            message: persistence.err(),
+        },
+    ];
+    let ready = checks.iter().all(|check| check.ok);
+    if !ready {
+        log::warn!("Server isn't ready: {checks:?}");
+    }
+    let status = if ready {
+        Status::Ok
     } else {
-        displays.html.closed.clone()
+        Status::ServiceUnavailable
     };
-    (ContentType::HTML, status)
+    (status, Json(Readiness { ready, checks }))
 }
 
-/// OPTION fallback handler required for CORS
+/// OPTION fallback handler, the preflight responses are set by the [`Cors`](crate::cors::Cors) fairing
 #[options("/<_..>")]
 pub fn options_catch_all() {}
@@ -0,0 +1,134 @@
+use rocket::serde::{
+    json::{serde_json, Value},
+    Deserialize, Serialize,
+};
+use spaceapi_dezentrale::sensors::Sensors;
+use std::hash::{Hash, Hasher};
+
+/// Sensor of the published template, addressed by its kind and optionally its name and location
+///
+/// The first sensor of the kind matching the name and location is updated.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SensorTarget {
+    /// Kind of the sensor as in the SpaceAPI, e.g. `temperature` or `door_locked`
+    #[serde(rename = "kind")]
+    pub kind: String,
+    #[serde(default, rename = "name")]
+    pub name: Option<String>,
+    #[serde(default, rename = "location")]
+    pub location: Option<String>,
+}
+
+impl std::fmt::Display for SensorTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        for label in [&self.name, &self.location].into_iter().flatten() {
+            write!(f, " {label}")?;
+        }
+        Ok(())
+    }
+}
+
+impl SensorTarget {
+    fn matches(&self, entry: &Value) -> bool {
+        let label = |name: &str| entry.get(name).and_then(Value::as_str);
+        self.name
+            .as_deref()
+            .map_or(true, |name| label("name") == Some(name))
+            && self
+                .location
+                .as_deref()
+                .map_or(true, |location| label("location") == Some(location))
+    }
+
+    /// The matching sensor of the JSON encoded `sensors`
+    fn find<'a>(&self, sensors: &'a mut Value) -> Option<&'a mut Value> {
+        sensors
+            .get_mut(&self.kind)?
+            .as_array_mut()?
+            .iter_mut()
+            .find(|entry| self.matches(entry))
+    }
+
+    /// Sets the value of the sensor in `sensors`, fails if it doesn't exist or the value doesn't fit
+    pub fn apply(&self, sensors: &mut Option<Sensors>, value: &Value) -> Result<(), String> {
+        let mut encoded = sensors
+            .as_ref()
+            .and_then(|sensors| serde_json::to_value(sensors).ok())
+            .unwrap_or(Value::Null);
+        let entry = self
+            .find(&mut encoded)
+            .ok_or_else(|| format!("Sensor `{self}` isn't part of the published sensors"))?;
+        entry["value"] = value.clone();
+        let updated = serde_json::from_value(encoded)
+            .map_err(|err| format!("Invalid value `{value}` of sensor `{self}`: {err}"))?;
+        *sensors = Some(updated);
+        Ok(())
+    }
+
+    /// Fails if the sensor isn't part of the template `sensors`
+    pub fn validate(&self, sensors: Option<&Sensors>) -> Result<(), String> {
+        let mut encoded = sensors
+            .and_then(|sensors| serde_json::to_value(sensors).ok())
+            .unwrap_or(Value::Null);
+        match self.find(&mut encoded) {
+            Some(_) => Ok(()),
+            None => Err(format!("Sensor `{self}` isn't part of the published sensors")),
+        }
+    }
+}
+
+/// Parses a reported value, JSON numbers and booleans, a string otherwise
+pub fn parse_value(text: &str) -> Value {
+    let text = text.trim();
+    match serde_json::from_str::<Value>(text) {
+        Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+        _ => Value::String(text.to_string()),
+    }
+}
+
+/// Latest values reported by the sources, published instead of the values of the template
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SensorValues(Vec<(SensorTarget, Value)>);
+
+impl SensorValues {
+    /// Sets the value of `target`, returns whether it changed
+    pub fn set(&mut self, target: SensorTarget, value: Value) -> bool {
+        match self.0.iter_mut().find(|(known, _)| *known == target) {
+            Some((_, known)) if *known == value => false,
+            Some((_, known)) => {
+                *known = value;
+                true
+            }
+            None => {
+                self.0.push((target, value));
+                true
+            }
+        }
+    }
+
+    /// Removes the value of `target`, the value of the template is published again
+    pub fn remove(&mut self, target: &SensorTarget) -> bool {
+        let count = self.0.len();
+        self.0.retain(|(known, _)| known != target);
+        self.0.len() != count
+    }
+
+    /// Sets all values in `sensors`, values which don't fit were rejected when they were reported
+    pub fn apply(&self, sensors: &mut Option<Sensors>) {
+        for (target, value) in &self.0 {
+            if let Err(err) = target.apply(sensors, value) {
+                log::debug!("{err}");
+            }
+        }
+    }
+}
+
+impl Hash for SensorValues {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (target, value) in &self.0 {
+            target.hash(state);
+            value.to_string().hash(state);
+        }
+    }
+}
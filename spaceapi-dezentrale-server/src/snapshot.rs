@@ -0,0 +1,167 @@
+use crate::{
+    cache::Validators,
+    config::PrivacyConfig,
+    display::{DisplayContext, DisplayFormat, StatusDisplays},
+    routes::StatusLite,
+    state::{SpaceState, SpaceStatus},
+};
+use rocket::{
+    http::ContentType,
+    request::Request,
+    response::{self, Responder, Response},
+    serde::json::serde_json,
+};
+use std::{io::Cursor, sync::Arc, time::SystemTime};
+use tokio::sync::watch;
+
+/// Receiver of the latest snapshot, routes only borrow it instead of locking the state
+pub type StatusReceiver = watch::Receiver<Arc<StatusSnapshot>>;
+
+/// Pre-rendered body shared by all responses of a snapshot
+#[derive(Debug, Clone)]
+pub struct Rendered(pub ContentType, pub Arc<[u8]>);
+
+impl<'r> Responder<'r, 'static> for Rendered {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        let Rendered(content_type, body) = self;
+        Response::build()
+            .header(content_type)
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+/// Responses of the status routes for one state of the space
+#[derive(Debug)]
+pub struct StatusSnapshot {
+    pub validators: Validators,
+    /// Status of the space, the badges are rendered on request
+    pub status: SpaceStatus,
+    /// Body of `/spaceapi/v14`
+    pub spaceapi: Rendered,
+    /// Body of `/status/json`
+    pub json: Rendered,
+    /// Body of `/status/text`, if the template could be rendered
+    pub text: Option<Rendered>,
+    /// Body of `/status/html`, if the template could be rendered
+    pub html: Option<Rendered>,
+}
+
+/// Renders the status routes on every change of the state and publishes them via a watch channel
+pub struct StatusCache {
+    template: spaceapi_dezentrale::Status,
+    displays: StatusDisplays,
+    privacy: PrivacyConfig,
+    sender: watch::Sender<Arc<StatusSnapshot>>,
+}
+
+impl StatusCache {
+    pub fn new(
+        template: spaceapi_dezentrale::Status,
+        displays: StatusDisplays,
+        privacy: PrivacyConfig,
+        state: &SpaceState,
+        now: SystemTime,
+    ) -> Self {
+        let snapshot = render(&template, &displays, &privacy, state, None, now);
+        let (sender, _) = watch::channel(Arc::new(snapshot));
+        StatusCache {
+            template,
+            displays,
+            privacy,
+            sender,
+        }
+    }
+
+    pub fn subscribe(&self) -> StatusReceiver {
+        self.sender.subscribe()
+    }
+
+    /// Replaces the snapshot with one of the given state at `now`
+    pub fn update(&self, state: &SpaceState, now: SystemTime) {
+        let previous = self.sender.borrow().validators.clone();
+        self.sender.send_replace(Arc::new(render(
+            &self.template,
+            &self.displays,
+            &self.privacy,
+            state,
+            Some(&previous),
+            now,
+        )));
+    }
+}
+
+fn render_display(
+    displays: &StatusDisplays,
+    context: &DisplayContext<'_>,
+    format: DisplayFormat,
+    content_type: ContentType,
+) -> Option<Rendered> {
+    match displays.render(format, context) {
+        Ok(display) => Some(Rendered(content_type, display.into_bytes().into())),
+        Err(err) => {
+            log::error!("{err}");
+            None
+        }
+    }
+}
+
+fn render_json<T: rocket::serde::Serialize>(value: &T) -> Rendered {
+    let body = serde_json::to_vec(value).expect("Serializable status");
+    Rendered(ContentType::JSON, body.into())
+}
+
+/// Renders all status routes for the state, without the fields hidden by `privacy`
+///
+/// The snapshot was last modified `now`, unless it publishes the same as the one of the `previous`
+/// validators.
+pub fn render(
+    template: &spaceapi_dezentrale::Status,
+    displays: &StatusDisplays,
+    privacy: &PrivacyConfig,
+    state: &SpaceState,
+    previous: Option<&Validators>,
+    now: SystemTime,
+) -> StatusSnapshot {
+    let trigger_person = state
+        .trigger_person
+        .as_deref()
+        .filter(|_| !privacy.hide_trigger_person);
+    // The closing soon warning isn't a message of a person
+    let message = state
+        .published_message()
+        .filter(|_| state.closing_soon.is_some() || !privacy.hide_message);
+
+    let mut status = template.clone();
+    status.api_compatibility = Some(vec![spaceapi_dezentrale::ApiVersion::V14]);
+    status.sensors = state.sensors(template);
+    if privacy.hide_sensors {
+        status.sensors = None;
+    }
+    status.state = Some(spaceapi_dezentrale::State {
+        open: state.status.open(),
+        lastchange: Some(crate::unix_timestamp(state.lastchange)),
+        trigger_person: trigger_person.map(str::to_string),
+        message: message.map(str::to_string),
+        ..spaceapi_dezentrale::State::default()
+    });
+    let lite = StatusLite {
+        open: state.status.open(),
+        state: state.status.name().to_string(),
+        since: crate::unix_timestamp(state.lastchange),
+        until: state.status.until().map(crate::unix_timestamp),
+    };
+    let mut context = DisplayContext::new(state, template);
+    context.trigger_person = trigger_person;
+    context.message = message;
+    context.sensors = status.sensors.as_ref();
+
+    StatusSnapshot {
+        validators: Validators::new(state, previous, now),
+        status: state.status,
+        spaceapi: render_json(&status),
+        json: render_json(&lite),
+        text: render_display(displays, &context, DisplayFormat::Text, ContentType::Text),
+        html: render_display(displays, &context, DisplayFormat::Html, ContentType::HTML),
+    }
+}
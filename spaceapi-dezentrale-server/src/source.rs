@@ -0,0 +1,99 @@
+use crate::{clock::Clock, state::SpaceGuard};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// Task updating a command source or a poller periodically
+pub struct Runner {
+    /// Name of the source in the logs
+    name: String,
+    handle: Mutex<Option<JoinHandle<()>>>,
+    shutdown: Arc<Notify>,
+}
+
+impl Runner {
+    /// Starts the task calling `update` right away and every `interval`
+    pub fn start<F, U>(name: String, clock: Arc<dyn Clock>, interval: Duration, update: F) -> Self
+    where
+        F: Fn() -> U + Send + 'static,
+        U: Future<Output = ()> + Send,
+    {
+        let shutdown = Arc::new(Notify::new());
+        let stop = shutdown.clone();
+        let task_name = name.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let next = clock.now() + interval;
+                tokio::select! {
+                    _ = update() => {}
+                    _ = stop.notified() => break,
+                }
+                tokio::select! {
+                    _ = clock.sleep_until(next) => {}
+                    _ = stop.notified() => break,
+                }
+            }
+            log::debug!("{task_name} stopped");
+        });
+        Runner {
+            name,
+            handle: Mutex::new(Some(handle)),
+            shutdown,
+        }
+    }
+
+    /// Stops the task and waits till it finished, a running update is dropped
+    pub async fn stop(&self) {
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            self.shutdown.notify_one();
+            if let Err(err) = handle.await {
+                log::error!("{} failed: {err}", self.name);
+            }
+        }
+    }
+}
+
+/// Tasks of all command sources and pollers of the server
+pub struct Runners(pub Vec<Runner>);
+
+impl Runners {
+    pub async fn stop(&self) {
+        for runner in &self.0 {
+            runner.stop().await;
+        }
+    }
+}
+
+/// State a source requested last, a state is only requested again when it changes
+///
+/// So the space can still be changed by other clients in between. A rejected request, e.g. while
+/// a mode is set, isn't remembered and is sent again with the next update.
+#[derive(Debug, Default)]
+pub struct RequestedState(Mutex<Option<bool>>);
+
+impl RequestedState {
+    /// Requests to open or close the space if `open` differs from the last accepted request
+    pub async fn request(&self, space: &SpaceGuard, open: bool, source: &str) {
+        if *self.0.lock().unwrap() == Some(open) {
+            return;
+        }
+        let result = if open {
+            space.request_open(None, None).await
+        } else {
+            space.request_close(None, None).await
+        };
+        match result {
+            Ok(()) => *self.0.lock().unwrap() = Some(open),
+            Err(err) => log::info!("{source} can't change the space: {err}"),
+        }
+    }
+
+    /// Forgets the last request, so the next state is requested in any case
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
@@ -1,92 +1,1115 @@
+use crate::{
+    clock::{Clock, SystemClock},
+    closing::{self, ClosingSoon},
+    config::{CheckInConfig, PrivacyConfig, StaleStatus},
+    display::StatusDisplays,
+    history::{History, StateChange},
+    incoming::WebhookMatch,
+    presence::{CheckIn, NetworkDevices, Presence},
+    sensors::{SensorTarget, SensorValues},
+    snapshot::{StatusCache, StatusReceiver},
+};
 use std::{
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{Duration, SystemTime},
 };
-use tokio::sync::RwLock;
+use tokio::{
+    sync::{broadcast, Notify, RwLock, RwLockReadGuard},
+    task::JoinHandle,
+};
 
-pub enum LastOpenRequest {
+/// Longest duration of leases and check-ins, longer ones are bounded to it
+pub const MAX_DURATION: Duration = Duration::from_secs(100 * 365 * 24 * 3600);
+
+/// Status of the space
+///
+/// Every status can change to every other one, except for the modes [`SpaceStatus::Maintenance`]
+/// and [`SpaceStatus::PrivateEvent`], which only change to another mode:
+///
+/// | From                           | To                                       |
+/// |--------------------------------|------------------------------------------|
+/// | `Closed`, `Unknown`            | `Open`, `KeepOpen`, `Unknown`, `Closed`  |
+/// | `Open`, `KeepOpen`             | `Open`, `KeepOpen`, `Unknown`, `Closed`  |
+/// | all                            | `Maintenance`, `PrivateEvent`            |
+///
+/// A `KeepOpen` status changes to `Closed` when its deadline passes. A mode only ends when it's
+/// lifted or its end passes, the space is closed then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpaceStatus {
+    Closed,
+    /// Open till the space is closed explicitly
     Open,
+    /// Open till the deadline, unless the request is repeated
     KeepOpen(SystemTime),
+    /// Nobody knows, published as `open: null`
+    Unknown,
+    /// Closed for maintenance till the end, if any, open requests are rejected
+    Maintenance(Option<SystemTime>),
+    /// Closed to the public for a private event till the end, if any, open requests are rejected
+    PrivateEvent(Option<SystemTime>),
+}
+
+impl SpaceStatus {
+    pub fn is_open(&self) -> bool {
+        matches!(self, SpaceStatus::Open | SpaceStatus::KeepOpen(_))
+    }
+
+    /// Published value of `state.open`, `None` if the status is unknown
+    pub fn open(&self) -> Option<bool> {
+        match self {
+            SpaceStatus::Unknown => None,
+            status => Some(status.is_open()),
+        }
+    }
+
+    /// Mode set by an admin, which overrides all open requests
+    pub fn mode(&self) -> Option<Mode> {
+        match self {
+            SpaceStatus::Maintenance(_) => Some(Mode::Maintenance),
+            SpaceStatus::PrivateEvent(_) => Some(Mode::PrivateEvent),
+            _ => None,
+        }
+    }
+
+    pub fn is_mode(&self) -> bool {
+        self.mode().is_some()
+    }
+
+    /// End of the status, the deadline of a keep open request or the end of a mode
+    pub fn until(&self) -> Option<SystemTime> {
+        match *self {
+            SpaceStatus::KeepOpen(until) => Some(until),
+            SpaceStatus::Maintenance(until) | SpaceStatus::PrivateEvent(until) => until,
+            _ => None,
+        }
+    }
+
+    /// Name of the status, as used in the templates and the minimal JSON status
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpaceStatus::Closed => "closed",
+            SpaceStatus::Open => "open",
+            SpaceStatus::KeepOpen(_) => "keep_open",
+            SpaceStatus::Unknown => "unknown",
+            SpaceStatus::Maintenance(_) => Mode::Maintenance.name(),
+            SpaceStatus::PrivateEvent(_) => Mode::PrivateEvent.name(),
+        }
+    }
+
+    /// Validates the change to the `next` status
+    pub fn transition(&self, next: SpaceStatus) -> Result<SpaceStatus, String> {
+        if !self.is_mode() || next.is_mode() {
+            return Ok(next);
+        }
+        Err(format!(
+            "The space can't change from `{}` to `{}`",
+            self.name(),
+            next.name()
+        ))
+    }
+}
+
+/// Mode set by an admin, the space is published as closed while it lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Maintenance,
+    PrivateEvent,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Maintenance => "maintenance",
+            Mode::PrivateEvent => "private_event",
+        }
+    }
+
+    /// Status of the mode lasting till `until`, or till it's lifted
+    pub fn status(&self, until: Option<SystemTime>) -> SpaceStatus {
+        match self {
+            Mode::Maintenance => SpaceStatus::Maintenance(until),
+            Mode::PrivateEvent => SpaceStatus::PrivateEvent(until),
+        }
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, <Self as std::str::FromStr>::Err> {
+        match s {
+            "maintenance" => Ok(Mode::Maintenance),
+            "private_event" => Ok(Mode::PrivateEvent),
+            other => Err(format!("Unknown mode `{other}`")),
+        }
+    }
+}
+
+/// Amount of events kept for slow subscribers
+const EVENT_CAPACITY: usize = 16;
+
+/// Event of the space, e.g. sent to webhooks
+#[derive(Debug, Clone, PartialEq, Eq, rocket::serde::Serialize)]
+#[serde(tag = "event")]
+pub enum SpaceEvent {
+    /// The keep open request ends soon, it can still be extended
+    #[serde(rename = "closing_soon")]
+    ClosingSoon {
+        /// Timestamp (UTC) till the space stays open
+        open_till: u64,
+        minutes: u64,
+        message: String,
+    },
+}
+
+/// Warning published before a keep open request expires
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClosingWarning {
+    pub open_till: SystemTime,
+    /// Minutes left, rounded up
+    pub minutes: u64,
+    /// Published instead of the message of the state
+    pub message: String,
+}
+
+/// Open or close request which waits till it's stable, see [`SpaceGuard::with_debounce`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingChange {
+    pub status: SpaceStatus,
+    pub trigger_person: Option<String>,
+    pub message: Option<String>,
+    /// Time of the first request of the change
+    pub since: SystemTime,
+}
+
+/// Keep open request of a device, the space stays open while any lease is alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    /// Name given by the client, the name of the API key otherwise
+    pub id: String,
+    /// Name of the API key which requested the lease
+    pub key: String,
+    pub until: SystemTime,
 }
 
 pub struct SpaceState {
-    pub open: bool,
-    pub last_open_request: LastOpenRequest,
+    pub status: SpaceStatus,
+    /// Leases of a `KeepOpen` status, the status lasts till the latest one
+    pub leases: Vec<Lease>,
     pub keep_open_interval: Duration,
+    /// Time of the last change of the published `open` value
+    pub lastchange: SystemTime,
+    /// Person who triggered the last change
+    pub trigger_person: Option<String>,
+    /// Message attached to the current state
+    pub message: Option<String>,
+    /// Warning while a keep open request ends soon
+    pub closing_soon: Option<ClosingWarning>,
+    /// Change which isn't published till it's stable
+    pub pending: Option<PendingChange>,
+    /// Latest changes between open and closed, with debouncing the requested ones
+    pub history: History,
+    /// Members checked in, `None` if check-in is disabled
+    pub presence: Option<Presence>,
+    /// Latest values reported by the sensor sources
+    pub sensor_values: SensorValues,
+}
+
+impl SpaceState {
+    pub fn new(keep_open_interval: Duration) -> Self {
+        SpaceState {
+            status: SpaceStatus::Closed,
+            leases: vec![],
+            keep_open_interval,
+            lastchange: SystemTime::now(),
+            trigger_person: None,
+            message: None,
+            closing_soon: None,
+            pending: None,
+            history: History::default(),
+            presence: None,
+            sensor_values: SensorValues::default(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status.is_open()
+    }
+
+    /// Deadline of the current keep open request, if any
+    pub fn keep_open_until(&self) -> Option<SystemTime> {
+        match self.status {
+            SpaceStatus::KeepOpen(open_till) => Some(open_till),
+            _ => None,
+        }
+    }
+
+    /// Closes the space if the keep open request expired at `now`, returns whether it closed
+    pub fn close_if_expired(&mut self, now: SystemTime) -> Result<bool, String> {
+        match self.keep_open_until() {
+            Some(open_till) if now >= open_till => {
+                self.change(SpaceStatus::Closed, None, None, now)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Published message, the closing soon warning takes precedence
+    pub fn published_message(&self) -> Option<&str> {
+        match &self.closing_soon {
+            Some(warning) => Some(&warning.message),
+            None => self.message.as_deref(),
+        }
+    }
+
+    /// Sensors of `template` with the reported values and the people present
+    pub fn sensors(
+        &self,
+        template: &spaceapi_dezentrale::Status,
+    ) -> Option<spaceapi_dezentrale::sensors::Sensors> {
+        let mut sensors = template.sensors.clone();
+        self.sensor_values.apply(&mut sensors);
+        if let Some(presence) = &self.presence {
+            presence.publish(&mut sensors);
+        }
+        sensors
+    }
+
+    /// Whether the latest request opened the space, published or not
+    fn requested_open(&self) -> bool {
+        match &self.pending {
+            Some(pending) => pending.status.is_open(),
+            None => self.is_open(),
+        }
+    }
+
+    /// Leases which are alive at `now`
+    pub fn active_leases(&self, now: SystemTime) -> impl Iterator<Item = &Lease> {
+        self.leases.iter().filter(move |lease| lease.until > now)
+    }
+
+    /// Changes the status, the history only records changes between open and not open
+    ///
+    /// All leases are dropped unless the space is kept open.
+    fn change(
+        &mut self,
+        status: SpaceStatus,
+        trigger_person: Option<String>,
+        message: Option<String>,
+        now: SystemTime,
+    ) -> Result<(), String> {
+        let status = self.status.transition(status)?;
+        self.replace(status, trigger_person, message, now);
+        Ok(())
+    }
+
+    /// Closes the space at the end of a mode, which no other change may end
+    fn end_mode(&mut self, now: SystemTime) {
+        self.replace(SpaceStatus::Closed, None, None, now);
+    }
+
+    /// Replaces the status without validating the change
+    fn replace(
+        &mut self,
+        status: SpaceStatus,
+        trigger_person: Option<String>,
+        message: Option<String>,
+        now: SystemTime,
+    ) {
+        self.pending = None;
+        if let Some(presence) = self.presence.as_mut() {
+            presence.opened = false;
+        }
+        let previous = std::mem::replace(&mut self.status, status);
+        if !matches!(status, SpaceStatus::KeepOpen(_)) {
+            self.leases.clear();
+        }
+        // A warning is only valid for its deadline
+        if previous != status {
+            self.closing_soon = None;
+        }
+        self.trigger_person = trigger_person;
+        self.message = message;
+        if previous.open() != status.open() {
+            self.lastchange = now;
+        }
+        if previous.is_open() != status.is_open() {
+            self.history.record(StateChange {
+                timestamp: crate::unix_timestamp(now),
+                open: status.is_open(),
+                trigger_person: self.trigger_person.clone(),
+                message: self.message.clone(),
+                published: true,
+            });
+        }
+    }
+}
+
+/// Task closing the space when a keep open request expires
+struct Scheduler {
+    handle: JoinHandle<()>,
+    shutdown: Arc<Notify>,
 }
 
 #[derive(Clone)]
-pub struct SpaceGuard(Arc<RwLock<SpaceState>>);
+pub struct SpaceGuard {
+    state: Arc<RwLock<SpaceState>>,
+    scheduler: Arc<Mutex<Option<Scheduler>>>,
+    /// Wakes the scheduler to pick up a new deadline
+    rearm: Arc<Notify>,
+    status_cache: Option<Arc<StatusCache>>,
+    closing_soon: Option<Arc<ClosingSoon>>,
+    /// Time an open or close request has to be stable till it's published
+    debounce: Duration,
+    check_in: CheckInConfig,
+    events: broadcast::Sender<SpaceEvent>,
+    clock: Arc<dyn Clock>,
+}
 
 impl SpaceGuard {
-    pub fn new(keep_open_interval: Duration) -> Self {
-        SpaceGuard(Arc::new(RwLock::new(SpaceState {
-            open: false,
-            last_open_request: LastOpenRequest::Open,
-            keep_open_interval,
-        })))
+    pub fn new(keep_open_interval: Duration, history: History) -> Self {
+        SpaceGuard::with_clock(keep_open_interval, history, Arc::new(SystemClock))
+    }
+
+    /// State using the given clock for all timestamps and deadlines
+    pub fn with_clock(keep_open_interval: Duration, history: History, clock: Arc<dyn Clock>) -> Self {
+        let mut state = SpaceState::new(keep_open_interval);
+        state.lastchange = clock.now();
+        state.history = history;
+        if state.history.last().is_some_and(|change| change.open) {
+            // The server always starts closed, so an open interval from a previous run ends now
+            state.history.record(StateChange {
+                timestamp: crate::unix_timestamp(state.lastchange),
+                open: false,
+                trigger_person: None,
+                message: Some("Server restarted".to_string()),
+                published: true,
+            });
+        }
+        SpaceGuard {
+            state: Arc::new(RwLock::new(state)),
+            scheduler: Arc::new(Mutex::new(None)),
+            rearm: Arc::new(Notify::new()),
+            status_cache: None,
+            closing_soon: None,
+            debounce: Duration::ZERO,
+            check_in: CheckInConfig::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            clock,
+        }
+    }
+
+    /// Current time of the clock of the state
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
     }
 
-    pub async fn open(&self) {
-        let mut space = self.0.write().await;
-        space.open = true;
-        space.last_open_request = LastOpenRequest::Open;
-        log::debug!("Space set open");
+    /// Renders the status routes on every change, without the fields hidden by `privacy`, see
+    /// [`SpaceGuard::subscribe`]
+    pub fn with_status_cache(
+        mut self,
+        template: spaceapi_dezentrale::Status,
+        displays: StatusDisplays,
+        privacy: PrivacyConfig,
+    ) -> Self {
+        let state = self.state.try_read().expect("Unshared state");
+        let cache = StatusCache::new(template, displays, privacy, &state, self.clock.now());
+        drop(state);
+        self.status_cache = Some(Arc::new(cache));
+        self
     }
 
-    pub async fn close(&self) {
-        let mut space = self.0.write().await;
-        space.open = false;
-        log::debug!("Space set closed");
+    /// Warns before keep open requests expire
+    pub fn with_closing_soon(mut self, closing_soon: ClosingSoon) -> Self {
+        self.closing_soon = Some(Arc::new(closing_soon));
+        self
+    }
+
+    /// Publishes open and close requests of clients only if they aren't reverted within `debounce`
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Lets members check in, the amount of present people is published as sensor
+    pub fn with_check_in(mut self, check_in: CheckInConfig) -> Self {
+        let mut state = self.state.try_write().expect("Unshared state");
+        state.presence = Some(Presence::default());
+        if let Some(cache) = &self.status_cache {
+            cache.update(&state, self.clock.now());
+        }
+        drop(state);
+        self.check_in = check_in;
+        self
+    }
+
+    /// Receiver of the events of the space
+    pub fn subscribe_events(&self) -> broadcast::Receiver<SpaceEvent> {
+        self.events.subscribe()
+    }
+
+    /// Receiver of the pre-rendered status routes, if the status cache is used
+    pub fn subscribe(&self) -> Option<StatusReceiver> {
+        self.status_cache.as_ref().map(|cache| cache.subscribe())
+    }
+
+    /// Publishes a changed state, called while the write lock is held to keep the order
+    fn changed(&self, state: &SpaceState) {
+        if let Some(cache) = &self.status_cache {
+            cache.update(state, self.clock.now());
+        }
+        // Stores a permit if the scheduler isn't waiting right now, so no change gets lost
+        self.rearm.notify_one();
+    }
+
+    /// Read access to the current state
+    pub async fn state(&self) -> RwLockReadGuard<'_, SpaceState> {
+        self.state.read().await
+    }
+
+    /// Changes the status and publishes it
+    async fn change(
+        &self,
+        status: SpaceStatus,
+        trigger_person: Option<String>,
+        message: Option<String>,
+    ) -> Result<(), String> {
+        let mut space = self.state.write().await;
+        space.change(status, trigger_person, message, self.clock.now())?;
+        self.changed(&space);
+        log::debug!("Space set {}", status.name());
+        Ok(())
+    }
+
+    pub async fn open(&self, trigger_person: Option<String>, message: Option<String>) -> Result<(), String> {
+        self.change(SpaceStatus::Open, trigger_person, message).await
+    }
+
+    pub async fn close(&self, trigger_person: Option<String>, message: Option<String>) -> Result<(), String> {
+        self.change(SpaceStatus::Closed, trigger_person, message).await
+    }
+
+    /// Opens or closes the space on request of a client, debounced if configured
+    ///
+    /// A change between open and closed is pending till no contrary request arrived within the
+    /// debounce time, only then it's published with the time of its first request. All requests
+    /// changing between open and closed are recorded in the history right away as unpublished
+    /// changes, the feed and the calendar only show the published ones.
+    async fn request(
+        &self,
+        status: SpaceStatus,
+        trigger_person: Option<String>,
+        message: Option<String>,
+    ) -> Result<(), String> {
+        let mut space = self.state.write().await;
+        let now = self.clock.now();
+        self.apply_request(&mut space, status, trigger_person, message, now)
+    }
+
+    /// Applies the request to the locked `space`, see [`SpaceGuard::request`]
+    fn apply_request(
+        &self,
+        space: &mut SpaceState,
+        status: SpaceStatus,
+        trigger_person: Option<String>,
+        message: Option<String>,
+        now: SystemTime,
+    ) -> Result<(), String> {
+        if self.debounce.is_zero() {
+            space.change(status, trigger_person, message, now)?;
+            self.changed(space);
+            log::debug!("Space set {}", status.name());
+            return Ok(());
+        }
+        space.status.transition(status)?;
+        if space.requested_open() != status.is_open() {
+            space.history.record(StateChange {
+                timestamp: crate::unix_timestamp(now),
+                open: status.is_open(),
+                trigger_person: trigger_person.clone(),
+                message: message.clone(),
+                published: false,
+            });
+        }
+        if space.is_open() == status.is_open() {
+            if space.pending.take().is_some() {
+                log::debug!("Pending change reverted within the debounce time");
+            }
+            // Nothing to publish if the request only confirms the state
+            if space.status != status || space.trigger_person != trigger_person || space.message != message {
+                space.change(status, trigger_person, message, now)?;
+                self.changed(space);
+            }
+        } else {
+            // Repeated requests don't delay the change
+            let since = match &space.pending {
+                Some(pending) if pending.status.is_open() == status.is_open() => pending.since,
+                _ => now,
+            };
+            space.pending = Some(PendingChange {
+                status,
+                trigger_person,
+                message,
+                since,
+            });
+            self.rearm.notify_one();
+            log::debug!("Space will be set {} if the request is stable", status.name());
+        }
+        Ok(())
+    }
+
+    /// Opens the space on request of a client, see [`SpaceGuard::with_debounce`]
+    pub async fn request_open(
+        &self,
+        trigger_person: Option<String>,
+        message: Option<String>,
+    ) -> Result<(), String> {
+        self.request(SpaceStatus::Open, trigger_person, message).await
+    }
+
+    /// Closes the space on request of a client, see [`SpaceGuard::with_debounce`]
+    pub async fn request_close(
+        &self,
+        trigger_person: Option<String>,
+        message: Option<String>,
+    ) -> Result<(), String> {
+        self.request(SpaceStatus::Closed, trigger_person, message).await
+    }
+
+    /// Publishes the pending change if it was stable till `now`
+    pub async fn check_pending(&self, now: SystemTime) {
+        let mut space = self.state.write().await;
+        let Some(pending) = space.pending.clone() else {
+            return;
+        };
+        if now < pending.since + self.debounce {
+            return;
+        }
+        let PendingChange {
+            status,
+            trigger_person,
+            message,
+            since,
+        } = pending;
+        match space.change(status, trigger_person, message, since) {
+            Ok(()) => {
+                self.changed(&space);
+                log::debug!("Space set {} after the debounce time", status.name());
+            }
+            Err(err) => {
+                space.pending = None;
+                log::warn!("Dropped pending change: {err}");
+            }
+        }
+    }
+
+    /// Publishes that nobody knows whether the space is open
+    pub async fn unknown(
+        &self,
+        trigger_person: Option<String>,
+        message: Option<String>,
+    ) -> Result<(), String> {
+        self.change(SpaceStatus::Unknown, trigger_person, message).await
+    }
+
+    /// Publishes the stale status of a source which timed out, unless a mode is set
+    ///
+    /// Returns the mode which is kept, checked and changed under one lock, so a mode set
+    /// meanwhile isn't overwritten.
+    pub async fn stale(&self, status: StaleStatus, message: Option<String>) -> Result<Option<Mode>, String> {
+        let mut space = self.state.write().await;
+        if let Some(mode) = space.status.mode() {
+            return Ok(Some(mode));
+        }
+        let status = match status {
+            StaleStatus::Unknown => SpaceStatus::Unknown,
+            StaleStatus::Closed => SpaceStatus::Closed,
+        };
+        space.change(status, None, message, self.clock.now())?;
+        self.changed(&space);
+        log::debug!("Space set {}", status.name());
+        Ok(None)
+    }
+
+    /// Sets the mode `mode` till `until`, open requests are rejected while it lasts
+    ///
+    /// The space is published as closed with the message, all leases and pending changes are
+    /// dropped. The mode lasts till it's lifted or its end passes, which is at most
+    /// [`MAX_DURATION`] away.
+    pub async fn set_mode(
+        &self,
+        mode: Mode,
+        message: Option<String>,
+        until: Option<SystemTime>,
+    ) -> Result<(), String> {
+        let until = until.map(|until| until.min(self.clock.now() + MAX_DURATION));
+        self.change(mode.status(until), None, message).await
+    }
+
+    /// Lifts the current mode by closing the space, returns whether a mode was set
+    pub async fn lift_mode(&self) -> Result<bool, String> {
+        let mut space = self.state.write().await;
+        let Some(mode) = space.status.mode() else {
+            return Ok(false);
+        };
+        space.end_mode(self.clock.now());
+        self.changed(&space);
+        log::info!("Mode {} lifted", mode.name());
+        Ok(true)
+    }
+
+    /// Lifts the mode if its end passed at `now`
+    pub async fn check_mode(&self, now: SystemTime) {
+        let mut space = self.state.write().await;
+        let Some(mode) = space.status.mode() else {
+            return;
+        };
+        if space.status.until().map_or(true, |until| now < until) {
+            return;
+        }
+        space.end_mode(now);
+        self.changed(&space);
+        log::info!("Mode {} ended", mode.name());
+    }
+
+    /// Checks in `member` till the check-in timeout
+    ///
+    /// A repeated check-in renews the previous one. With `auto_open` the first check-in opens the
+    /// space, naming the member as trigger person if `public` is set.
+    pub async fn check_in(&self, member: &str, public: bool) -> Result<CheckIn, String> {
+        let mut space = self.state.write().await;
+        let now = self.clock.now();
+        let until = now + self.check_in.timeout.min(MAX_DURATION);
+        let is_open = space.is_open();
+        let Some(presence) = space.presence.as_mut() else {
+            return Err("Check-in is disabled".to_string());
+        };
+        let since = presence
+            .check_ins
+            .iter()
+            .find(|check_in| check_in.member == member)
+            .map_or(now, |check_in| check_in.since);
+        presence.check_ins.retain(|check_in| check_in.member != member);
+        let first = presence.check_ins.is_empty();
+        let check_in = CheckIn {
+            member: member.to_string(),
+            public,
+            since,
+            until,
+        };
+        presence.check_ins.push(check_in.clone());
+        if self.check_in.auto_open && first && !is_open {
+            let trigger_person = public.then(|| member.to_string());
+            match space.change(SpaceStatus::Open, trigger_person, None, now) {
+                Ok(()) => {
+                    if let Some(presence) = space.presence.as_mut() {
+                        presence.opened = true;
+                    }
+                }
+                Err(err) => log::info!("Space not opened on check-in: {err}"),
+            }
+        }
+        self.changed(&space);
+        log::debug!("Member `{member}` checked in till {until:?}");
+        Ok(check_in)
+    }
+
+    /// Checks out `member`, returns whether the member was checked in
+    ///
+    /// With `auto_open` the last check-out closes the space, unless it's kept open.
+    pub async fn check_out(&self, member: &str) -> Result<bool, String> {
+        let mut space = self.state.write().await;
+        let Some(presence) = space.presence.as_mut() else {
+            return Err("Check-in is disabled".to_string());
+        };
+        let count = presence.check_ins.len();
+        presence.check_ins.retain(|check_in| check_in.member != member);
+        if presence.check_ins.len() == count {
+            return Ok(false);
+        }
+        self.checked_out(&mut space, self.clock.now());
+        self.changed(&space);
+        log::debug!("Member `{member}` checked out");
+        Ok(true)
+    }
+
+    /// Closes the space after the last check-out, if configured and the check-ins opened it
+    fn checked_out(&self, space: &mut SpaceState, now: SystemTime) {
+        let opened = space
+            .presence
+            .as_ref()
+            .is_some_and(|presence| presence.check_ins.is_empty() && presence.opened);
+        if self.check_in.auto_open && opened && space.status == SpaceStatus::Open {
+            if let Err(err) = space.change(SpaceStatus::Closed, None, None, now) {
+                log::error!("Can't close space after the last check-out: {err}");
+            }
+        }
+    }
+
+    /// Members checked in now
+    pub async fn check_ins(&self) -> Vec<CheckIn> {
+        let space = self.state.read().await;
+        space
+            .presence
+            .as_ref()
+            .map(|presence| presence.check_ins.clone())
+            .unwrap_or_default()
+    }
+
+    /// Publishes the devices found on the network, see
+    /// [`NetworkPresence`](crate::network::NetworkPresence)
+    pub async fn set_network_devices(&self, devices: NetworkDevices) {
+        let mut space = self.state.write().await;
+        let presence = space.presence.get_or_insert_with(Presence::default);
+        if presence.network == devices {
+            return;
+        }
+        presence.network = devices;
+        self.changed(&space);
+    }
+
+    /// Applies the matches of the incoming webhook `name`, its leases are held with the name as id
+    /// and key
+    ///
+    /// All requested changes are checked and applied under one lock, so either all or none of them
+    /// are applied.
+    pub async fn apply_webhook(&self, name: &str, matches: Vec<WebhookMatch>) -> Result<(), String> {
+        let mut space = self.state.write().await;
+        let now = self.clock.now();
+        for status in matches
+            .iter()
+            .filter_map(|webhook_match| webhook_match.status(now))
+        {
+            space.status.transition(status)?;
+        }
+        for webhook_match in matches {
+            match webhook_match {
+                WebhookMatch::Open {
+                    trigger_person,
+                    message,
+                } => self.apply_request(&mut space, SpaceStatus::Open, trigger_person, message, now)?,
+                WebhookMatch::Close {
+                    trigger_person,
+                    message,
+                } => self.apply_request(&mut space, SpaceStatus::Closed, trigger_person, message, now)?,
+                WebhookMatch::KeepOpen(duration) => {
+                    let duration = if duration.is_zero() {
+                        space.keep_open_interval
+                    } else {
+                        duration
+                    };
+                    self.hold_lease(&mut space, name, name, duration, now)?;
+                }
+                WebhookMatch::Sensor(target, value) => {
+                    if space.sensor_values.set(target, value) {
+                        self.changed(&space);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes the value of a sensor reported by a source
+    pub async fn set_sensor(&self, target: SensorTarget, value: rocket::serde::json::Value) {
+        let mut space = self.state.write().await;
+        if space.sensor_values.set(target, value) {
+            self.changed(&space);
+        }
+    }
+
+    /// Publishes the value of the template for a sensor again, e.g. when its source is stale
+    pub async fn clear_sensor(&self, target: &SensorTarget) {
+        let mut space = self.state.write().await;
+        if space.sensor_values.remove(target) {
+            self.changed(&space);
+        }
+    }
+
+    /// Checks out all members whose check-in timed out at `now`
+    pub async fn check_check_ins(&self, now: SystemTime) {
+        let mut space = self.state.write().await;
+        let Some(presence) = space.presence.as_mut() else {
+            return;
+        };
+        let count = presence.check_ins.len();
+        presence.check_ins.retain(|check_in| now < check_in.until);
+        let timed_out = count - presence.check_ins.len();
+        if timed_out == 0 {
+            return;
+        }
+        self.checked_out(&mut space, now);
+        self.changed(&space);
+        log::info!("{timed_out} check-ins timed out");
     }
 
     pub async fn is_open(&self) -> bool {
-        let space = self.0.read().await;
-        log::trace!("Space status requested and is {}", space.open);
-        space.open
+        let space = self.state.read().await;
+        log::trace!("Space status requested and is {}", space.status.name());
+        space.is_open()
+    }
+
+    /// Keeps the space open with the lease `id` of the API key `key`, returns the end of the lease
+    ///
+    /// The lease lasts for `duration`, the keep open interval by default, at most [`MAX_DURATION`].
+    /// Leases are kept per key, a lease of the key with the same id is replaced.
+    pub async fn keep_open(
+        &self,
+        id: &str,
+        key: &str,
+        duration: Option<Duration>,
+    ) -> Result<SystemTime, String> {
+        let mut space = self.state.write().await;
+        let now = self.clock.now();
+        let duration = duration.unwrap_or(space.keep_open_interval);
+        self.hold_lease(&mut space, id, key, duration, now)
+    }
+
+    /// Keeps the space open with the lease `id` of the API key `key` for `duration`, unless a client
+    /// opened it without a deadline
+    ///
+    /// An alive lease is only extended once it ends within `renew_within`, so repeated calls don't
+    /// change the published status every time. Returns the new end of the lease, if any.
+    pub async fn renew_lease(
+        &self,
+        id: &str,
+        key: &str,
+        duration: Duration,
+        renew_within: Duration,
+    ) -> Result<Option<SystemTime>, String> {
+        let mut space = self.state.write().await;
+        let now = self.clock.now();
+        if space.status == SpaceStatus::Open {
+            return Ok(None);
+        }
+        let alive = space
+            .active_leases(now)
+            .any(|lease| lease.id == id && lease.key == key && lease.until > now + renew_within);
+        if alive {
+            return Ok(None);
+        }
+        self.hold_lease(&mut space, id, key, duration, now).map(Some)
+    }
+
+    /// Adds the lease to the locked `space`, see [`SpaceGuard::keep_open`]
+    fn hold_lease(
+        &self,
+        space: &mut SpaceState,
+        id: &str,
+        key: &str,
+        duration: Duration,
+        now: SystemTime,
+    ) -> Result<SystemTime, String> {
+        let until = now + duration.min(MAX_DURATION);
+        let mut leases: Vec<Lease> = space
+            .active_leases(now)
+            .filter(|lease| lease.id != id || lease.key != key)
+            .cloned()
+            .collect();
+        leases.push(Lease {
+            id: id.to_string(),
+            key: key.to_string(),
+            until,
+        });
+        let open_till = leases.iter().map(|lease| lease.until).max().unwrap_or(until);
+        // Repeated requests keep the person and message of the opening
+        let (trigger_person, message) = if space.is_open() {
+            (space.trigger_person.clone(), space.message.clone())
+        } else {
+            (None, None)
+        };
+        space.change(SpaceStatus::KeepOpen(open_till), trigger_person, message, now)?;
+        space.leases = leases;
+        self.changed(space);
+        log::trace!("Lease `{id}` keeps the space open till {until:?}, the space till {open_till:?}");
+        Ok(until)
     }
 
-    pub async fn keep_open(&self) -> SystemTime {
-        let mut space = self.0.write().await;
-        space.open = true;
-        let now = SystemTime::now();
-        let open_till = now.checked_add(space.keep_open_interval).unwrap();
-        space.last_open_request = LastOpenRequest::KeepOpen(open_till);
-        log::trace!("Space requested to keep open and it will till {open_till:?}");
-        open_till
+    /// Default duration of keep open requests
+    pub async fn keep_open_interval(&self) -> Duration {
+        self.state.read().await.keep_open_interval
     }
 
+    /// Leases which are alive now
+    pub async fn leases(&self) -> Vec<Lease> {
+        let space = self.state.read().await;
+        space.active_leases(self.clock.now()).cloned().collect()
+    }
+
+    /// Revokes the lease `id` of the API key `key`, the space closes if it was the last one
+    ///
+    /// Returns whether the lease was alive.
+    pub async fn revoke_lease(&self, id: &str, key: &str) -> Result<bool, String> {
+        let mut space = self.state.write().await;
+        let now = self.clock.now();
+        let owned = |lease: &&Lease| lease.id == id && lease.key == key;
+        if !space.active_leases(now).any(|lease| owned(&lease)) {
+            return Ok(false);
+        }
+        let leases: Vec<Lease> = space
+            .active_leases(now)
+            .filter(|lease| !owned(lease))
+            .cloned()
+            .collect();
+        let (trigger_person, message) = (space.trigger_person.clone(), space.message.clone());
+        match leases.iter().map(|lease| lease.until).max() {
+            Some(open_till) => {
+                space.change(SpaceStatus::KeepOpen(open_till), trigger_person, message, now)?;
+                space.leases = leases;
+            }
+            None => space.change(SpaceStatus::Closed, None, None, now)?,
+        }
+        self.changed(&space);
+        log::debug!("Lease `{id}` of `{key}` revoked");
+        Ok(true)
+    }
+
+    /// Closes the space if the keep open request expired at `now`
+    ///
+    /// Checked and closed under one lock, so a lease renewed meanwhile isn't closed.
     pub async fn check_keep_open(&self, now: SystemTime) {
         log::trace!("Checking keep open status at {now:?}");
-        let space = self.0.read().await;
-        if !space.open {
+        let mut space = self.state.write().await;
+        match space.close_if_expired(now) {
+            Ok(true) => {
+                self.changed(&space);
+                log::debug!(
+                    "Space set {} after keep open request expired",
+                    SpaceStatus::Closed.name()
+                );
+            }
+            Ok(false) => {}
+            Err(err) => log::error!("Can't close space after keep open request expired: {err}"),
+        }
+    }
+
+    /// Publishes the closing soon warning if the keep open request ends soon at `now`
+    pub async fn check_closing_soon(&self, now: SystemTime) {
+        let Some(closing_soon) = &self.closing_soon else {
+            return;
+        };
+        let mut space = self.state.write().await;
+        let Some(open_till) = space.keep_open_until() else {
+            return;
+        };
+        if !closing_soon.is_warning(now, open_till) {
             return;
         }
-        if let LastOpenRequest::KeepOpen(open_till) = space.last_open_request {
-            if now <= open_till {
+        let minutes = closing::minutes_left(now, open_till);
+        if space
+            .closing_soon
+            .as_ref()
+            .is_some_and(|warning| warning.minutes == minutes)
+        {
+            return;
+        }
+        let message = match closing_soon.message(minutes) {
+            Ok(message) => message,
+            Err(err) => {
+                log::error!("{err}");
                 return;
             }
-        } else {
-            return;
+        };
+        let started = space.closing_soon.is_none();
+        space.closing_soon = Some(ClosingWarning {
+            open_till,
+            minutes,
+            message: message.clone(),
+        });
+        self.changed(&space);
+        if started {
+            log::info!("Space closes in {minutes} minutes");
+            let _ = self.events.send(SpaceEvent::ClosingSoon {
+                open_till: crate::unix_timestamp(open_till),
+                minutes,
+                message,
+            });
         }
-        // drop to free lock
-        drop(space);
-        self.close().await;
     }
 
-    pub async fn start_scheduler(&self, tick_interval: Duration) {
+    /// Next time the scheduler has to act: the end of the keep open request, a change of its
+    /// warning, the end of the debounce time of a pending change, the end of a mode or the
+    /// timeout of a check-in
+    async fn next_deadline(&self) -> Option<SystemTime> {
+        let space = self.state().await;
+        let keep_open = space.keep_open_until().map(|open_till| match &self.closing_soon {
+            Some(closing_soon) => closing_soon.next_tick(self.clock.now(), open_till).min(open_till),
+            None => open_till,
+        });
+        let pending = space
+            .pending
+            .as_ref()
+            .map(|pending| pending.since + self.debounce);
+        let mode = space.status.until().filter(|_| space.status.is_mode());
+        let check_in = space.presence.as_ref().and_then(Presence::next_timeout);
+        keep_open
+            .into_iter()
+            .chain(pending)
+            .chain(mode)
+            .chain(check_in)
+            .min()
+    }
+
+    /// Starts the task closing the space when the keep open deadline passes
+    ///
+    /// The task sleeps until the current deadline, or the next change of the closing soon warning,
+    /// and is woken up on every change of the state.
+    pub fn start_scheduler(&self) {
         let instance = self.clone();
-        tokio::spawn(async move {
+        let shutdown = Arc::new(Notify::new());
+        let stop = shutdown.clone();
+        let handle = tokio::spawn(async move {
             loop {
-                instance.check_keep_open(SystemTime::now()).await;
-                tokio::time::sleep(tick_interval).await;
+                // A change of the state may start the warning right away
+                instance.check_closing_soon(instance.clock.now()).await;
+                let deadline = instance.next_deadline().await;
+                let expired = async {
+                    match deadline {
+                        Some(deadline) => instance.clock.sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = expired => {
+                        let now = instance.clock.now();
+                        instance.check_keep_open(now).await;
+                        instance.check_pending(now).await;
+                        instance.check_mode(now).await;
+                        instance.check_check_ins(now).await;
+                        instance.check_closing_soon(now).await;
+                    }
+                    _ = instance.rearm.notified() => {}
+                    _ = stop.notified() => break,
+                }
             }
+            log::debug!("Keep open scheduler stopped");
         });
+        *self.scheduler.lock().unwrap() = Some(Scheduler { handle, shutdown });
+    }
+
+    /// Stops the scheduler and waits till it finished
+    pub async fn stop_scheduler(&self) {
+        let scheduler = self.scheduler.lock().unwrap().take();
+        if let Some(scheduler) = scheduler {
+            scheduler.shutdown.notify_one();
+            if let Err(err) = scheduler.handle.await {
+                log::error!("Keep open scheduler failed: {err}");
+            }
+        }
+    }
+
+    /// Whether the scheduler task was started and is still running
+    pub fn is_scheduler_running(&self) -> bool {
+        self.scheduler
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|scheduler| !scheduler.handle.is_finished())
     }
 }
 
 impl Default for SpaceGuard {
     fn default() -> Self {
-        SpaceGuard::new(Duration::from_secs(300))
+        SpaceGuard::new(Duration::from_secs(300), History::default())
     }
 }
@@ -0,0 +1,102 @@
+use crate::state::SpaceEvent;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast, Notify},
+    task::JoinHandle,
+};
+
+/// Task sending the events
+struct Sender {
+    handle: JoinHandle<()>,
+    shutdown: Arc<Notify>,
+}
+
+/// Outgoing webhook, which POSTs the events of the space as JSON to an URL
+#[derive(Clone)]
+pub struct Webhook {
+    url: String,
+    client: reqwest::Client,
+    sender: Arc<Mutex<Option<Sender>>>,
+}
+
+impl Webhook {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self, String> {
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(format!("{}/{}", crate::SOFTWARE, crate::VERSION))
+            .timeout(timeout)
+            .build()
+            .map_err(|err| format!("Can't build webhook client: {err}"))?;
+        Ok(Webhook {
+            url: url.to_string(),
+            client,
+            sender: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Sends one event, fails unless the receiver answers with a success status
+    pub async fn send(&self, event: &SpaceEvent) -> Result<(), String> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(event)
+            .send()
+            .await
+            .map_err(|err| format!("Can't send webhook to `{}`: {err}", self.url))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Webhook `{}` answered with {}",
+                self.url,
+                response.status()
+            ))
+        }
+    }
+
+    /// Starts the task sending all events of `events` till the sender is dropped or it's stopped
+    pub fn start(&self, mut events: broadcast::Receiver<SpaceEvent>) {
+        let instance = self.clone();
+        let shutdown = Arc::new(Notify::new());
+        let stop = shutdown.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    event = events.recv() => event,
+                    _ = stop.notified() => break,
+                };
+                match event {
+                    Ok(event) => {
+                        tokio::select! {
+                            result = instance.send(&event) => {
+                                if let Err(err) = result {
+                                    log::warn!("{err}");
+                                }
+                            }
+                            _ = stop.notified() => break,
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Webhook `{}` skipped {skipped} events", instance.url);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            log::debug!("Webhook `{}` stopped", instance.url);
+        });
+        *self.sender.lock().unwrap() = Some(Sender { handle, shutdown });
+    }
+
+    /// Stops the task and waits till it finished
+    pub async fn stop(&self) {
+        let sender = self.sender.lock().unwrap().take();
+        if let Some(sender) = sender {
+            sender.shutdown.notify_one();
+            if let Err(err) = sender.handle.await {
+                log::error!("Webhook `{}` failed: {err}", self.url);
+            }
+        }
+    }
+}